@@ -0,0 +1,92 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, BenchmarkId, Criterion};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use ssith::prover::{Prover, ProverMode};
+use ssith::*;
+
+/// The system allocator that records the current and the peak heap usage.
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+/// Run the three steps of the prover on fixed challenges.
+fn prove(prover: &Prover, param: Param) {
+    let chal1: Vec<_> = (0..param.get_rep_param()).collect();
+    let chal2 = vec![0; param.get_rep_param()];
+    let mut state = prover.step1();
+    prover.step2(&mut state, &chal1).unwrap();
+    // an abort is decided once the instances in J are expanded,
+    // so it does not change the peak much
+    let _ = prover.step3(&mut state, &chal2);
+}
+
+/// The peak heap usage of one session above the usage before it.
+fn peak_memory(prover: &Prover, param: Param) -> usize {
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    prove(prover, param);
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+fn make_prover(param: Param, mode: ProverMode) -> Prover {
+    let mut rng = ChaChaRng::seed_from_u64(0);
+    Prover::new(&mut rng, param).with_mode(mode)
+}
+
+fn report_peak_memory() {
+    for (name, param) in [("default", Param::default()), ("L1_FAST", Param::L1_FAST)] {
+        for mode in [ProverMode::Full, ProverMode::Lean] {
+            let prover = make_prover(param, mode);
+            println!(
+                "peak memory of {name} in {mode:?} mode: {} bytes",
+                peak_memory(&prover, param)
+            );
+        }
+    }
+}
+
+fn bench_modes(c: &mut Criterion) {
+    let param = Param::default();
+    let mut group = c.benchmark_group("prover");
+    group.sample_size(10);
+    for mode in [ProverMode::Full, ProverMode::Lean] {
+        let prover = make_prover(param, mode);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{mode:?}")),
+            &prover,
+            |b, prover| b.iter(|| prove(prover, param)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_modes);
+
+fn main() {
+    report_peak_memory();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
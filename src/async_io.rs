@@ -0,0 +1,274 @@
+use std::{io, marker::PhantomData, sync::Arc, time::Duration};
+
+use rand_core::{CryptoRng, RngCore};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    io::check_frame_size,
+    prover::Prover,
+    session::{Action, ProverSession, VerifierSession},
+    verifier::Verifier,
+    InternalError, ProverMsg, SecurityParams, VerifierMsg, L1,
+};
+
+/// A codec that sends messages of type `S` and receives messages of type `R`
+/// on an async stream, e.g., `tokio::net::TcpStream`.
+/// The frames are the same as in `io::wrap_tcpstream`,
+/// i.e., a little endian u64 length followed by the bincode encoding,
+/// and both reject frames larger than 16 MiB,
+/// so either side of a connection can use either transport.
+pub struct Framed<T, S, R> {
+    stream: T,
+    _marker: PhantomData<fn(S) -> R>,
+}
+
+impl<T, S, R> Framed<T, S, R>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    S: Serialize,
+    R: DeserializeOwned,
+{
+    pub fn new(stream: T) -> Self {
+        Self {
+            stream,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    pub async fn send(&mut self, msg: &S) -> io::Result<()> {
+        let data = bincode::serialize(msg).map_err(io::Error::other)?;
+        self.stream.write_u64_le(data.len() as u64).await?;
+        self.stream.write_all(&data).await?;
+        self.stream.flush().await
+    }
+
+    pub async fn recv(&mut self) -> io::Result<R> {
+        let n = check_frame_size(self.stream.read_u64_le().await?)?;
+        let mut value_buf = vec![0u8; n];
+        self.stream.read_exact(&mut value_buf).await?;
+        bincode::deserialize(&value_buf).map_err(io::Error::other)
+    }
+
+    /// Close the write half of the stream.
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.stream.shutdown().await
+    }
+}
+
+/// The async counterpart of `prover::IProver`,
+/// it runs the protocol on a stream without a thread per connection.
+/// The steps of the prover run on the blocking thread pool of tokio,
+/// so they do not stall the other tasks of the executor.
+pub struct AsyncIProver<T, S: SecurityParams = L1> {
+    prover: Prover<S>,
+    framed: Framed<T, ProverMsg<S>, VerifierMsg>,
+}
+
+impl<T, S> AsyncIProver<T, S>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    S: SecurityParams,
+{
+    pub fn new(prover: Prover<S>, stream: T) -> Self {
+        Self {
+            prover,
+            framed: Framed::new(stream),
+        }
+    }
+
+    /// Run the handshake and the protocol and output the verdict of the verifier,
+    /// if an instance in J aborts given L the prover restarts by sending a new first message.
+    /// The stream is shut down at the end of the run.
+    pub async fn run(mut self) -> Result<bool, InternalError> {
+        let mut session = ProverSession::from_prover(self.prover);
+        let mut msg = session.start();
+        while let Some(prover_msg) = msg.take() {
+            self.framed.send(&prover_msg).await?;
+            let verifier_msg = self.framed.recv().await?;
+            (session, msg) = tokio::task::spawn_blocking(move || {
+                let reply = session.handle(verifier_msg);
+                (session, reply)
+            })
+            .await
+            .map_err(|_| InternalError::BlockingTask)?;
+        }
+        let accepted = session.finish()?;
+        self.framed.shutdown().await?;
+        Ok(accepted)
+    }
+}
+
+/// The async counterpart of `verifier::IVerifier`,
+/// it runs the protocol on a stream without a thread per connection.
+/// The openings are checked on the blocking thread pool of tokio,
+/// so a large proof does not stall the other tasks of the executor.
+pub struct AsyncIVerifier<T, S: SecurityParams = L1> {
+    verifier: Arc<Verifier<S>>,
+    framed: Framed<T, VerifierMsg, ProverMsg<S>>,
+    timeout: Option<Duration>,
+}
+
+impl<T, S> AsyncIVerifier<T, S>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    S: SecurityParams,
+{
+    pub fn new(verifier: Verifier<S>, stream: T) -> Self {
+        Self {
+            verifier: Arc::new(verifier),
+            framed: Framed::new(stream),
+            timeout: None,
+        }
+    }
+
+    /// Limit the duration of a run, `InternalError::Timeout` is returned
+    /// if the prover does not finish the protocol within `timeout`.
+    /// A check of the openings that is already running is not cancelled,
+    /// its result is dropped.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub async fn run<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<bool, InternalError> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.run_inner(rng))
+                .await
+                .map_err(|_| InternalError::Timeout)?,
+            None => self.run_inner(rng).await,
+        }
+    }
+
+    async fn run_inner<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<bool, InternalError> {
+        let mut session = VerifierSession::new(&self.verifier, rng);
+        while !session.is_finished() {
+            let action = match session.take_transcript(self.framed.recv().await?) {
+                Ok(transcript) => {
+                    let verifier = self.verifier.clone();
+                    let accepted =
+                        tokio::task::spawn_blocking(move || transcript.verify(&verifier))
+                            .await
+                            .map_err(|_| InternalError::BlockingTask)?;
+                    session.conclude(accepted)
+                }
+                Err(msg) => session.handle(msg),
+            };
+            match action {
+                Action::Send(msg) | Action::Finish(msg) => self.framed.send(&msg).await?,
+                Action::Close => {}
+            }
+        }
+        session.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::{client::Outcome, client::ProverClient, Param};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_tcp_proof() {
+        let param = Param::default();
+        let mut rng = ChaChaRng::from_entropy();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // many provers on one task each, and no thread per connection
+        let provers: Vec<_> = (0..8).map(|_| Prover::<L1>::new(&mut rng, param)).collect();
+        let instances: Vec<_> = provers.iter().map(|p| p.get_instance().clone()).collect();
+        let server = tokio::spawn(async move {
+            let mut sessions = vec![];
+            for instance in instances {
+                let (stream, _) = listener.accept().await.unwrap();
+                sessions.push(tokio::spawn(async move {
+                    let verifier = Verifier::new(instance, param);
+                    let mut iverifier =
+                        AsyncIVerifier::new(verifier, stream).with_timeout(Duration::from_secs(60));
+                    iverifier.run(&mut ChaChaRng::from_entropy()).await
+                }));
+            }
+            sessions
+        });
+
+        // connect in order so that every session gets the right instance
+        let mut clients = vec![];
+        for prover in provers {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            clients.push(tokio::spawn(async move {
+                AsyncIProver::new(prover, stream).run().await
+            }));
+        }
+        for client in clients {
+            assert_eq!(client.await.unwrap(), Ok(true));
+        }
+        for session in server.await.unwrap() {
+            assert_eq!(session.await.unwrap(), Ok(true));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mixed_transports() {
+        let param = Param::default();
+        let mut rng = ChaChaRng::from_entropy();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // the thread-based client talks to the async verifier
+        let client = thread::spawn(move || ProverClient::prove_to(addr, prover));
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut iverifier = AsyncIVerifier::new(verifier, stream);
+        assert_eq!(iverifier.run(&mut rng).await, Ok(true));
+        assert_eq!(client.join().unwrap(), Ok(Outcome::Accepted));
+    }
+
+    #[tokio::test]
+    async fn test_async_errors() {
+        let param = Param::default();
+        let mut rng = ChaChaRng::from_entropy();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
+
+        // a prover that never sends anything times out
+        let (verifier_end, _prover_end) = tokio::io::duplex(1024);
+        let mut iverifier =
+            AsyncIVerifier::new(verifier, verifier_end).with_timeout(Duration::from_millis(50));
+        assert_eq!(iverifier.run(&mut rng).await, Err(InternalError::Timeout));
+
+        // a huge length prefix is rejected before anything is allocated
+        let (mut verifier_end, prover_end) = tokio::io::duplex(1024);
+        let mut framed = Framed::<_, ProverMsg, VerifierMsg>::new(prover_end);
+        verifier_end.write_u64_le(u64::MAX).await.unwrap();
+        assert_eq!(
+            framed.recv().await.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // the prover fails on a closed stream
+        let (verifier_end, prover_end) = tokio::io::duplex(1024);
+        drop(verifier_end);
+        assert_eq!(
+            AsyncIProver::new(prover, prover_end).run().await,
+            Err(InternalError::Io(io::ErrorKind::BrokenPipe))
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::net::{TcpStream, ToSocketAddrs};
+
+use crate::{
+    io::wrap_tcpstream,
+    prover::{IProver, Prover},
+    InternalError, ProverMsg, SecurityParams, VerifierMsg,
+};
+
+/// The verdict of the verifier on an interactive proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Accepted,
+    Rejected,
+}
+
+/// A prover that runs the interactive protocol with a verifier over TCP,
+/// e.g., with `server::VerifierServer`.
+pub struct ProverClient;
+
+impl ProverClient {
+    /// Connect to the verifier at `addr`, prove the instance of `prover`
+    /// and wait for the verdict of the verifier.
+    /// The connection is closed and its threads are joined on every path.
+    pub fn prove_to<S: SecurityParams, A: ToSocketAddrs>(
+        addr: A,
+        prover: Prover<S>,
+    ) -> Result<Outcome, InternalError> {
+        let stream = TcpStream::connect(addr)?;
+        let (tx, rx, shutdown_sender, handle) = wrap_tcpstream::<ProverMsg<S>, VerifierMsg>(stream);
+
+        // the prover must outlive the shutdown, otherwise the writer thread
+        // stops on the closed channel before the shutdown is sent
+        let mut iprover = IProver::from_prover(prover, tx, rx);
+        let res = iprover.blocking_run();
+        // this fails if the writer thread has already stopped on an error
+        let _ = shutdown_sender.send(());
+        let io_res = handle.join().expect("tcp thread panicked");
+
+        match (res, io_res) {
+            // the channels are closed because of the connection error
+            (Err(InternalError::RecvError(_) | InternalError::SendError), Err(e)) => Err(e.into()),
+            (res, _) => res.map(|accepted| {
+                if accepted {
+                    Outcome::Accepted
+                } else {
+                    Outcome::Rejected
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::TcpListener, thread, time::Duration};
+
+    use crossbeam::channel::unbounded;
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::{
+        handshake::HelloReject, server::VerifierServer, witness_instance_from_seed, Key, Param,
+        Witness, L1,
+    };
+
+    #[test]
+    fn test_prove_to() {
+        let param = Param::default();
+        let mut rng = ChaChaRng::from_entropy();
+        let (witness, instance) = witness_instance_from_seed::<L1>(&Key::<L1>::default(), param);
+        let prover =
+            Prover::from_witness_instance(witness.clone(), instance.clone(), [1; 16].into(), param)
+                .unwrap();
+        let mut bad_witness = witness.get_bits().to_vec();
+        bad_witness[0] ^= 1;
+        let cheater = Prover::from_witness_instance_unchecked(
+            Witness::from_bits(bad_witness).unwrap(),
+            instance.clone(),
+            [2; 16].into(),
+            param,
+        );
+        let other = Prover::<L1>::new(&mut rng, param);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (report_s, report_r) = unbounded();
+        let server = VerifierServer::new(instance, param)
+            .with_timeout(Duration::from_secs(10))
+            .spawn(listener, report_s)
+            .unwrap();
+        let addr = server.local_addr();
+
+        assert_eq!(ProverClient::prove_to(addr, prover), Ok(Outcome::Accepted));
+        assert_eq!(report_r.recv().unwrap().outcome, Ok(true));
+        assert_eq!(ProverClient::prove_to(addr, cheater), Ok(Outcome::Rejected));
+        assert_eq!(report_r.recv().unwrap().outcome, Ok(false));
+
+        // both sides know why the handshake is rejected
+        let reason = HelloReject::Instance;
+        assert_eq!(
+            ProverClient::prove_to(addr, other),
+            Err(InternalError::HandshakeRejected(reason.clone()))
+        );
+        assert_eq!(
+            report_r.recv().unwrap().outcome,
+            Err(InternalError::HandshakeRejected(reason))
+        );
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_prove_to_errors() {
+        let param = Param::default();
+        let mut rng = ChaChaRng::from_entropy();
+
+        // nobody listens on the address
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert_eq!(
+            ProverClient::prove_to(addr, Prover::<L1>::new(&mut rng, param)),
+            Err(InternalError::Io(std::io::ErrorKind::ConnectionRefused))
+        );
+
+        // the verifier closes the connection before the verdict
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hdl = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            drop(stream);
+        });
+        assert!(ProverClient::prove_to(addr, Prover::<L1>::new(&mut rng, param)).is_err());
+        hdl.join().unwrap();
+    }
+}
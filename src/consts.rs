@@ -1,15 +1,48 @@
-pub(crate) const KEY_SIZE: usize = 16;
-pub(crate) const BLOCK_SIZE: usize = 16;
-pub(crate) const OPENING_SIZE: usize = 16;
-pub(crate) const DIGEST_SIZE: usize = 32;
+/// The version of the byte encoding of non-interactive proofs.
+/// A later version must keep decoding the proofs of this one.
+pub(crate) const PROOF_VERSION: u8 = 1;
+
+/// The version of the interactive protocol, announced in `handshake::Hello`.
+pub(crate) const PROTOCOL_VERSION: u8 = 1;
 
 pub(crate) const PREFIX_H1_DELTA: [u8; 8] = *b"delta_rs";
 pub(crate) const PREFIX_H1_COM: [u8; 8] = *b"commitme";
 pub(crate) const PREFIX_H2: [u8; 8] = *b"h1s-----";
 pub(crate) const PREFIX_H3: [u8; 8] = *b"h3------";
 pub(crate) const PREFIX_H4: [u8; 8] = *b"h4------";
-pub(crate) const PREFIX_WITNESS: [u8; 8] = *b"witness-";
 pub(crate) const PREFIX_INSTANCE: [u8; 8] = *b"instance";
+pub(crate) const PREFIX_INSTANCE_SEED: [u8; 8] = *b"inst-sd-";
+pub(crate) const PREFIX_PRG: [u8; 8] = *b"prg-----";
 
 pub(crate) const PREFIX_FS_H1: [u8; 8] = *b"fs1-----";
-pub(crate) const PREFIX_FS_H2: [u8; 8] = *b"fs1-----";
+pub(crate) const PREFIX_FS_H2: [u8; 8] = *b"fs2-----";
+
+/// IVs for domain separation of the key generation PRGs,
+/// they are zero padded or truncated to the block size.
+pub(crate) const IV_KEYGEN: [u8; 16] = *b"keygen----------";
+pub(crate) const IV_KEYGEN_WITNESS: [u8; 16] = *b"keygen-witness--";
+pub(crate) const IV_KEYGEN_WEIGHTS: [u8; 16] = *b"keygen-weights--";
+
+/// The IV used to derive a fresh master seed when the prover restarts after an abort.
+pub(crate) const IV_RESTART: [u8; 16] = *b"restart---------";
+
+/// The largest SSP dimension of a `Param` or a deserialized `Instance`,
+/// so that a malformed input cannot make us expand a huge number of weights.
+pub(crate) const MAX_SSP_DIMENSION: usize = 1 << 16;
+
+/// The largest party count of a `Param`, the seed tree of the parties has as many leaves.
+pub(crate) const MAX_PARTY_COUNT: usize = 1 << 16;
+
+/// The largest cut and choose parameter of a `Param`,
+/// the seed tree of the master seeds has as many leaves.
+pub(crate) const MAX_CNC_PARAM: usize = 1 << 16;
+
+/// The largest number of restarts in one session, see `Param::max_restarts`.
+pub(crate) const MAX_RESTARTS: usize = 256;
+
+/// An honest prover runs out of restarts with probability at most 2^-40,
+/// unless `MAX_RESTARTS` is reached first.
+pub(crate) const RESTART_FAILURE_BITS: f64 = 40.0;
+
+pub(crate) const PREFIX_SIG_PK: [u8; 8] = *b"sig-pk--";
+pub(crate) const PREFIX_SIG_MSG: [u8; 8] = *b"sig-msg-";
@@ -1,4 +1,6 @@
+use crate::handshake::HelloReject;
 use crossbeam::channel;
+use std::io;
 use thiserror::Error;
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -7,18 +9,71 @@ pub enum InternalError {
     BadWitnessOrInstance,
     #[error("bad witness length")]
     BadWitnessLength,
+    #[error("bad witness value, must be 0 or 1")]
+    BadWitnessValue,
     #[error("bad instance length")]
     BadInstanceLength,
     #[error("bad abort param, must be less than 64")]
     BadAbortParam,
+    #[error("bad ssp dimension, must be positive and at most 2^16")]
+    BadSspDimension,
+    #[error("bad party count, must be at least 2 and at most 2^16")]
+    BadPartyCount,
+    #[error("bad cut and choose param, must be at most 2^16")]
+    BadCncParam,
+    #[error("bad rep param, must be positive and at most the cut and choose param")]
+    BadRepParam,
     #[error("bad challenge length")]
     BadChallengeLength,
+    #[error("challenge out of range")]
+    ChallengeOutOfRange,
+    #[error("duplicate index in the challenge")]
+    DuplicateChallenge,
+    #[error("an instance in the challenge aborts, the protocol must be restarted")]
+    Abort,
+    #[error("the prover state does not belong to the prover")]
+    BadProverState,
+    #[error("the prover state has already recorded another challenge")]
+    ConflictingChallenge,
+    #[error("the handshake is rejected: {0}")]
+    HandshakeRejected(HelloReject),
+    #[error("the session timed out")]
+    Timeout,
     #[error("protocol error, unexpected message")]
     ProtocolError,
+    #[error("unsupported proof version {0}")]
+    UnsupportedProofVersion(u8),
+    #[error("bad proof length")]
+    BadProofLength,
+    #[error("bad proof encoding")]
+    BadProofEncoding,
+    #[error("the proof is for security level {0}")]
+    SecurityLevelMismatch(u8),
+    #[error("the proof uses the hash function {0}")]
+    HashMismatch(u8),
+    #[error("the proof uses the PRG {0}")]
+    PrgMismatch(u8),
     #[error(transparent)]
     RecvError(#[from] channel::RecvError),
-    #[error(transparent)]
-    SendErrorProverMsg(#[from] channel::SendError<crate::ProverMsg>),
-    #[error(transparent)]
-    SendErrorVerifierMsg(#[from] channel::SendError<crate::VerifierMsg>),
+    #[error("sending on a disconnected channel")]
+    SendError,
+    #[error("a task on the blocking thread pool panicked or was cancelled")]
+    BlockingTask,
+    #[error("io error: {0}")]
+    Io(io::ErrorKind),
+}
+
+// The messages are generic over the security level,
+// so the message is dropped instead of being kept in the error.
+impl<T> From<channel::SendError<T>> for InternalError {
+    fn from(_: channel::SendError<T>) -> Self {
+        InternalError::SendError
+    }
+}
+
+// io::Error is neither Clone nor Eq, so only its kind is kept.
+impl From<io::Error> for InternalError {
+    fn from(e: io::Error) -> Self {
+        InternalError::Io(e.kind())
+    }
 }
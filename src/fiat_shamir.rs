@@ -1,92 +1,740 @@
-use std::thread;
+use std::io::{self, Read};
 
-use crossbeam::channel::unbounded;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use generic_array::{ArrayLength, GenericArray};
 use rand_chacha::ChaChaRng;
 use rand_core::{CryptoRng, RngCore, SeedableRng};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     consts::*,
     errors::InternalError,
     primitives::{fs_hash1, fs_hash2},
-    prover::{IProver, Prover},
+    prover::{AbortStats, Prover},
+    security::{digest_size, hash_id, key_size, prg_id},
+    seed_tree::SeedTree,
+    session::ProverSession,
     verifier::Verifier,
-    Param, ProverMsg, VerifierMsg,
+    DigestOutput, Instance, Key, Param, ProverMsg, SecurityParams, Step3Inner, VerifierMsg, L1,
 };
 
-pub struct NIProver {
-    prover: Prover,
+pub struct NIProver<S: SecurityParams = L1> {
+    prover: Prover<S>,
 }
 
-pub struct NIProverMsg {
-    step1: [u8; DIGEST_SIZE],
-    step2: ([u8; DIGEST_SIZE], Vec<[u8; BLOCK_SIZE]>),
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// The non-interactive proof, i.e., the three prover messages
+/// and the parameter that the proof is created for.
+/// The challenges are recomputed from the messages.
+pub struct NIProverMsg<S: SecurityParams = L1> {
+    param: Param,
+    step1: DigestOutput<S>,
+    step2: (DigestOutput<S>, Vec<Key<S>>),
+    step3: Vec<Step3Inner<S>>,
 }
 
-impl NIProver {
+impl<S: SecurityParams> NIProverMsg<S> {
+    pub fn get_param(&self) -> Param {
+        self.param
+    }
+
+    /// The maximum size of the byte encoding of a proof in bytes, it is
+    ///
+    /// 4 + 20 + 2 * DigestSize + 4 + P(M, tau) * KeySize
+    ///   + tau * (1 + ceil(log2 N) * KeySize + DigestSize + 8 * n + ceil(n / 8) + 8)
+    ///
+    /// where the first two terms are the header and the parameter,
+    /// the header is the version, the security level, the hash function and the PRG.
+    /// The master seeds of the instances that are not in J are revealed
+    /// by the seed tree punctured at J, P(M, tau) is the maximum number of nodes
+    /// for tau punctured leaves, which is about tau * log2(M / tau),
+    /// the actual number of nodes is encoded in four bytes.
+    /// Every repetition reveals the seed tree of the parties punctured at ell,
+    /// the number of nodes is encoded in one byte and it is smaller
+    /// than ceil(log2 N) for some ell if N is not a power of two.
+    /// The sizes are the associated types of `SecurityParams`,
+    /// i.e., seeds are 16 bytes for L1 and 32 bytes for L5,
+    /// and the digests are twice as large.
+    /// For example, the default parameter gives 27700 bytes for L1.
+    /// None is returned if the parameter is inconsistent,
+    /// i.e., tau > M or N = 0, or if the size overflows.
+    pub fn max_size(param: Param) -> Option<usize> {
+        let n = param.ssp_dimension;
+        if param.rep_param > param.cnc_param || param.party_count == 0 {
+            return None;
+        }
+        let mseed_path_len = SeedTree::<S>::max_open_size(param.cnc_param, param.rep_param);
+        let path_len = SeedTree::<S>::max_open_size(param.party_count, 1);
+        let per_rep = path_len
+            .checked_mul(key_size::<S>())?
+            .checked_add(1 + digest_size::<S>())?
+            .checked_add(n.checked_mul(8)?)?
+            .checked_add(n.div_ceil(8))?
+            .checked_add(8)?;
+        (PROOF_HEADER_SIZE + PARAM_ENCODING_SIZE + 2 * digest_size::<S>() + 4)
+            .checked_add(mseed_path_len.checked_mul(key_size::<S>())?)?
+            .checked_add(per_rep.checked_mul(param.rep_param)?)
+    }
+
+    /// A lower bound on the size of the byte encoding of a proof,
+    /// i.e., `max_size` where all the seed tree paths are empty.
+    fn min_size(param: Param) -> Option<usize> {
+        let n = param.ssp_dimension;
+        let per_rep = (1 + digest_size::<S>())
+            .checked_add(n.checked_mul(8)?)?
+            .checked_add(n.div_ceil(8))?
+            .checked_add(8)?;
+        (PROOF_HEADER_SIZE + PARAM_ENCODING_SIZE + 2 * digest_size::<S>() + 4)
+            .checked_add(per_rep.checked_mul(param.rep_param)?)
+    }
+
+    /// Encode the proof into its canonical byte representation.
+    /// All integers are encoded in little endian and
+    /// the masked witness is packed into bits.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Self::max_size(self.param).unwrap_or_default());
+        out.push(PROOF_VERSION);
+        out.push(S::ID);
+        out.push(hash_id::<S>());
+        out.push(prg_id::<S>());
+        for x in param_fields(&self.param) {
+            let x = u32::try_from(x).expect("parameter must fit in u32");
+            out.write_u32::<LittleEndian>(x).unwrap();
+        }
+        out.extend(&self.step1);
+
+        let (h_prime, mseed_path) = &self.step2;
+        out.extend(h_prime);
+        let mseed_path_len = u32::try_from(mseed_path.len()).expect("path must fit in u32");
+        out.write_u32::<LittleEndian>(mseed_path_len).unwrap();
+        for node in mseed_path {
+            out.extend(node);
+        }
+
+        for opening in &self.step3 {
+            let path_len = u8::try_from(opening.path.len()).expect("the tree must be shallow");
+            out.push(path_len);
+            for node in &opening.path {
+                out.extend(node);
+            }
+            out.extend(&opening.com);
+            for delta_r in &opening.delta_rs {
+                out.write_u64::<LittleEndian>(*delta_r).unwrap();
+            }
+            let mut packed = vec![0u8; opening.xs_tilde.len().div_ceil(8)];
+            for (j, x) in opening.xs_tilde.iter().enumerate() {
+                packed[j / 8] |= (x & 1) << (j % 8);
+            }
+            out.extend(packed);
+            out.write_u64::<LittleEndian>(opening.t_share).unwrap();
+        }
+        out
+    }
+
+    /// Decode a proof from its canonical byte representation.
+    /// The length must match exactly the size given by the encoded parameter
+    /// and the lengths of the seed tree paths.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        let (version, rest) = bytes.split_first().ok_or(InternalError::BadProofLength)?;
+        if *version != PROOF_VERSION {
+            return Err(InternalError::UnsupportedProofVersion(*version));
+        }
+        if rest.len() < PROOF_HEADER_SIZE - 1 {
+            return Err(InternalError::BadProofLength);
+        }
+        let (header, rest) = rest.split_at(PROOF_HEADER_SIZE - 1);
+        let (level, hash, prg) = (header[0], header[1], header[2]);
+        if level != S::ID {
+            return Err(InternalError::SecurityLevelMismatch(level));
+        }
+        if hash != hash_id::<S>() {
+            return Err(InternalError::HashMismatch(hash));
+        }
+        if prg != prg_id::<S>() {
+            return Err(InternalError::PrgMismatch(prg));
+        }
+        if rest.len() < PARAM_ENCODING_SIZE {
+            return Err(InternalError::BadProofLength);
+        }
+
+        let mut reader = rest;
+        let param = read_param(&mut reader)?;
+        // the repetitions are only allocated once the input is known to be long enough,
+        // so a short input cannot claim a huge tau
+        let min_size = Self::min_size(param).ok_or(InternalError::BadProofEncoding)?;
+        let max_size = Self::max_size(param).ok_or(InternalError::BadProofEncoding)?;
+        if bytes.len() < min_size || bytes.len() > max_size {
+            return Err(InternalError::BadProofLength);
+        }
+        let max_mseed_path_len = SeedTree::<S>::max_open_size(param.cnc_param, param.rep_param);
+        let max_path_len = SeedTree::<S>::max_open_size(param.party_count, 1);
+
+        // a short proof is detected by an unexpected end of the input
+        let mut decode = || -> io::Result<Self> {
+            let step1 = read_array(&mut reader)?;
+            let h_prime = read_array(&mut reader)?;
+            let mseed_path_len = reader.read_u32::<LittleEndian>()? as usize;
+            if mseed_path_len > max_mseed_path_len {
+                return Err(io::Error::from(io::ErrorKind::InvalidData));
+            }
+            let mseed_path = (0..mseed_path_len)
+                .map(|_| read_array(&mut reader))
+                .collect::<io::Result<_>>()?;
+
+            let mut step3 = Vec::with_capacity(param.rep_param);
+            for _ in 0..param.rep_param {
+                let path_len = reader.read_u8()? as usize;
+                if path_len > max_path_len {
+                    return Err(io::Error::from(io::ErrorKind::InvalidData));
+                }
+                let path = (0..path_len)
+                    .map(|_| read_array(&mut reader))
+                    .collect::<io::Result<_>>()?;
+                let com = read_array(&mut reader)?;
+                let delta_rs = (0..param.ssp_dimension)
+                    .map(|_| reader.read_u64::<LittleEndian>())
+                    .collect::<io::Result<_>>()?;
+                let mut packed = vec![0u8; param.ssp_dimension.div_ceil(8)];
+                reader.read_exact(&mut packed)?;
+                let xs_tilde = (0..param.ssp_dimension)
+                    .map(|j| (packed[j / 8] >> (j % 8)) & 1)
+                    .collect();
+                let t_share = reader.read_u64::<LittleEndian>()?;
+
+                // the padding bits must be zero for the encoding to be canonical
+                if param.ssp_dimension % 8 != 0
+                    && packed[packed.len() - 1] >> (param.ssp_dimension % 8) != 0
+                {
+                    return Err(io::Error::from(io::ErrorKind::InvalidData));
+                }
+
+                step3.push(Step3Inner {
+                    path,
+                    com,
+                    delta_rs,
+                    xs_tilde,
+                    t_share,
+                });
+            }
+
+            Ok(Self {
+                param,
+                step1,
+                step2: (h_prime, mseed_path),
+                step3,
+            })
+        };
+        let proof = decode().map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => InternalError::BadProofLength,
+            _ => InternalError::BadProofEncoding,
+        })?;
+        if !reader.is_empty() {
+            return Err(InternalError::BadProofLength);
+        }
+        Ok(proof)
+    }
+}
+
+impl<S: SecurityParams> Serialize for NIProverMsg<S> {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: Serializer,
+    {
+        if serializer.is_human_readable() {
+            hex::serde::serialize(self.to_bytes(), serializer)
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+impl<'de, S: SecurityParams> Deserialize<'de> for NIProverMsg<S> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes: Vec<u8> = if deserializer.is_human_readable() {
+            hex::serde::deserialize(deserializer)?
+        } else {
+            serde_bytes_buf(deserializer)?
+        };
+        Self::from_bytes(&bytes).map_err(de::Error::custom)
+    }
+}
+
+/// The number of bytes of the header, i.e., the version,
+/// the security level, the hash function and the PRG.
+const PROOF_HEADER_SIZE: usize = 4;
+
+/// The number of bytes used to encode `Param`, i.e., five u32.
+const PARAM_ENCODING_SIZE: usize = 20;
+
+/// The fields of `Param` in the order of the encoding.
+fn param_fields(param: &Param) -> [usize; 5] {
+    [
+        param.ssp_dimension,
+        param.party_count,
+        param.cnc_param,
+        param.abort_param,
+        param.rep_param,
+    ]
+}
+
+fn read_param<R: Read>(reader: &mut R) -> Result<Param, InternalError> {
+    let mut fields = [0usize; 5];
+    for x in &mut fields {
+        *x = reader
+            .read_u32::<LittleEndian>()
+            .map_err(|_| InternalError::BadProofLength)? as usize;
+    }
+    Param::new(fields[0], fields[1], fields[2], fields[3], fields[4])
+        .map_err(|_| InternalError::BadProofEncoding)
+}
+
+fn read_array<R: Read, N: ArrayLength<u8>>(reader: &mut R) -> io::Result<GenericArray<u8, N>> {
+    let mut out = GenericArray::default();
+    reader.read_exact(&mut out)?;
+    Ok(out)
+}
+
+/// Deserialize a byte buffer from a binary format,
+/// accepting both borrowed and owned bytes.
+fn serde_bytes_buf<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    struct BytesVisitor;
+
+    impl<'de> de::Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a byte array")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+    }
+
+    deserializer.deserialize_byte_buf(BytesVisitor)
+}
+
+/// Seed the challenge RNG with a digest,
+/// digests longer than the seed of the RNG are truncated.
+fn fs_rng<S: SecurityParams>(digest: &DigestOutput<S>) -> ChaChaRng {
+    let mut seed = <ChaChaRng as SeedableRng>::Seed::default();
+    let len = seed.len();
+    seed.copy_from_slice(&digest[..len]);
+    ChaChaRng::from_seed(seed)
+}
+
+/// Compute the first challenge J by hashing the context and the first prover message.
+fn fs_chal1<S: SecurityParams>(
+    verifier: &Verifier<S>,
+    ctx: &[u8],
+    h: &DigestOutput<S>,
+) -> Vec<usize> {
+    let mut rng1 = fs_rng::<S>(&fs_hash1::<S>(ctx, h));
+    verifier.step1(&mut rng1)
+}
+
+/// Compute the second challenge L by hashing the context and the second prover message.
+fn fs_chal2<S: SecurityParams>(
+    verifier: &Verifier<S>,
+    ctx: &[u8],
+    h_prime: &DigestOutput<S>,
+    mseeds: &[Key<S>],
+) -> Vec<usize> {
+    let mut rng2 = fs_rng::<S>(&fs_hash2::<S>(ctx, h_prime, mseeds));
+    verifier.step2(&mut rng2)
+}
+
+impl<S: SecurityParams> NIProver<S> {
     pub fn new<R: CryptoRng + RngCore>(rng: &mut R, param: Param) -> Self {
         Self {
             prover: Prover::new(rng, param),
         }
     }
 
+    pub fn from_prover(prover: Prover<S>) -> Self {
+        Self { prover }
+    }
+
     // Note that the rng is implicit in `prover`
-    pub fn prove(self) -> Result<NIProverMsg, InternalError> {
-        let (tx_p, rx_p) = unbounded();
-        let (tx_v, rx_v) = unbounded();
+    pub fn prove(self) -> Result<NIProverMsg<S>, InternalError> {
+        self.prove_with_context(&[])
+    }
 
-        let param = self.prover.get_param();
-        let verifier = Verifier::new(param);
-        let mut iprover = IProver::from_prover(self.prover, tx_p, rx_v);
+    /// Create the proof and output the abort statistics of the prover.
+    pub fn prove_with_stats(self) -> Result<(NIProverMsg<S>, AbortStats), InternalError> {
+        self.prove_inner(&[])
+    }
 
-        let handler = thread::spawn(move || iprover.blocking_run());
+    /// Create the proof where `ctx` is bound into both challenges.
+    pub(crate) fn prove_with_context(self, ctx: &[u8]) -> Result<NIProverMsg<S>, InternalError> {
+        self.prove_inner(ctx).map(|(proof, _)| proof)
+    }
 
-        // wait prover for its message h
-        let h = match rx_p.recv()? {
-            ProverMsg::Step1(h) => h,
-            _ => return Err(InternalError::ProtocolError),
-        };
+    /// Run the prover against the challenges computed from the transcript.
+    /// If an instance in J aborts given L, the prover restarts with a fresh master seed,
+    /// `InternalError::Abort` is returned after `Param::max_restarts` restarts.
+    fn prove_inner(mut self, ctx: &[u8]) -> Result<(NIProverMsg<S>, AbortStats), InternalError> {
+        let param = self.prover.get_param();
+        let verifier = Verifier::new(self.prover.get_instance().clone(), param);
+        let mut session = ProverSession::new(&mut self.prover);
 
-        // hash h, and use it to generate J
-        let fs_seed1 = fs_hash1(&h);
-        let mut rng1 = ChaChaRng::from_seed(fs_seed1);
-        tx_v.send(VerifierMsg::Step1(verifier.step1(&mut rng1)))?;
+        // the proof carries the parameter and the verifier knows the instance,
+        // so the handshake is accepted without being sent
+        session.start();
+        let mut msg = session.handle(VerifierMsg::Hello(Ok(())));
+        let mut step1 = None;
+        let mut step2 = None;
+        while let Some(prover_msg) = msg.take() {
+            let chal = match prover_msg {
+                // hash h, and use it to generate J,
+                // a new h means that the prover restarts
+                ProverMsg::Step1(h) => {
+                    let chal1 = fs_chal1(&verifier, ctx, &h);
+                    step1 = Some(h);
+                    VerifierMsg::Step1(chal1)
+                }
+                // hash the second message as seed for the second challenge L
+                ProverMsg::Step2((h_prime, mseed_path)) => {
+                    let chal2 = fs_chal2(&verifier, ctx, &h_prime, &mseed_path);
+                    step2 = Some((h_prime, mseed_path));
+                    VerifierMsg::Step2(chal2)
+                }
+                // put together the messages
+                ProverMsg::Step3(openings) => {
+                    let proof = NIProverMsg {
+                        param,
+                        step1: step1.ok_or(InternalError::ProtocolError)?,
+                        step2: step2.ok_or(InternalError::ProtocolError)?,
+                        step3: openings,
+                    };
+                    return Ok((proof, session.stats()));
+                }
+                ProverMsg::Hello(_) => return Err(InternalError::ProtocolError),
+            };
+            msg = session.handle(chal);
+        }
+        // the session only stops before the last message if it fails
+        Err(session
+            .finish()
+            .err()
+            .unwrap_or(InternalError::ProtocolError))
+    }
+}
 
-        // wait for prover for its second message
-        let (h_prime, mseeds) = match rx_p.recv()? {
-            ProverMsg::Step2(inner) => inner,
-            _ => return Err(InternalError::ProtocolError),
-        };
+pub struct NIVerifier;
 
-        // hash the second message as seed for the second challenge L
-        let fs_seed2 = fs_hash2(&h_prime, &mseeds);
-        let mut rng2 = ChaChaRng::from_seed(fs_seed2);
-        tx_v.send(VerifierMsg::Step2(verifier.step2(&mut rng2)))?;
+impl NIVerifier {
+    /// Verify a non-interactive proof for `instance`.
+    /// The challenges are recomputed in the same way as `NIProver::prove`.
+    /// An error is returned if the instance does not match the parameters.
+    pub fn verify<S: SecurityParams>(
+        instance: Instance<S>,
+        param: Param,
+        proof: &NIProverMsg<S>,
+    ) -> Result<bool, InternalError> {
+        Self::verify_with_context(instance, param, &[], proof)
+    }
 
-        // TODO not sure how to handle this error in thiserror
-        handler.join().unwrap()?;
+    /// Verify a proof where `ctx` is bound into both challenges.
+    pub(crate) fn verify_with_context<S: SecurityParams>(
+        instance: Instance<S>,
+        param: Param,
+        ctx: &[u8],
+        proof: &NIProverMsg<S>,
+    ) -> Result<bool, InternalError> {
+        if instance.weights.len() != param.ssp_dimension {
+            return Err(InternalError::BadInstanceLength);
+        }
+        param.validate()?;
+        if proof.param != param {
+            return Ok(false);
+        }
 
-        // put together the messages
-        Ok(NIProverMsg {
-            step1: h,
-            step2: (h_prime, mseeds),
-        })
+        let verifier = Verifier::new(instance, param);
+        let (h_prime, mseeds) = &proof.step2;
+        let chal1 = fs_chal1(&verifier, ctx, &proof.step1);
+        let chal2 = fs_chal2(&verifier, ctx, h_prime, mseeds);
+        Ok(verifier.verify(&proof.step1, &chal1, h_prime, mseeds, &chal2, &proof.step3))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "blake3")]
+    use crate::hash::Blake3;
+    use crate::hash::{Sha3_256, Shake128, Shake256};
+    #[cfg(feature = "chacha20")]
+    use crate::prg::ChaCha20;
+    use crate::prg::Shake;
+    use crate::L5;
     use rand_chacha::ChaChaRng;
     use rand_core::SeedableRng;
 
+    fn check_fs<S: SecurityParams>() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let niprover = NIProver::<S>::new(&mut rng, param);
+        let instance = niprover.prover.get_instance().clone();
+        let proof = niprover.prove().unwrap();
+        assert_eq!(
+            NIVerifier::verify(instance.clone(), param, &proof),
+            Ok(true)
+        );
+
+        // modifying the second message changes the second challenge
+        let mut bad_proof = proof.clone();
+        bad_proof.step2.1[0][0] ^= 1;
+        assert_eq!(
+            NIVerifier::verify(instance.clone(), param, &bad_proof),
+            Ok(false)
+        );
+
+        // the proof should not verify for another instance
+        let (_, other_instance) = crate::new_witness_instance::<S, _>(&mut rng, param);
+        assert_eq!(NIVerifier::verify(other_instance, param, &proof), Ok(false));
+
+        let short_param = Param {
+            ssp_dimension: 9,
+            ..param
+        };
+        assert_eq!(
+            NIVerifier::verify(instance, short_param, &proof),
+            Err(InternalError::BadInstanceLength)
+        );
+    }
+
     #[test]
     fn test_fs() {
+        check_fs::<L1>();
+        check_fs::<L5>();
+        check_fs::<L1<Shake128>>();
+        check_fs::<L5<Shake256>>();
+        check_fs::<L1<Sha3_256, Shake>>();
+        check_fs::<L5<Shake256, Shake>>();
+        #[cfg(feature = "blake3")]
+        {
+            check_fs::<L1<Blake3>>();
+            check_fs::<L5<Blake3>>();
+        }
+        #[cfg(feature = "chacha20")]
+        {
+            check_fs::<L1<Sha3_256, ChaCha20>>();
+            check_fs::<L5<Shake256, ChaCha20>>();
+        }
+    }
+
+    #[test]
+    fn test_restart() {
         let mut rng = ChaChaRng::from_entropy();
-        let param = Param::default();
-        let niprover = NIProver::new(&mut rng, param);
+        // about 40% of the instances abort
+        let param = Param::new(8, 2, 16, 4, 4).unwrap();
+        let niprover = NIProver::<L1>::new(&mut rng, param);
+        let instance = niprover.prover.get_instance().clone();
+        let (proof, stats) = niprover.prove_with_stats().unwrap();
+        assert_eq!(NIVerifier::verify(instance, param, &proof), Ok(true));
+        assert_eq!(
+            stats.instance_count,
+            (stats.restart_count + 1) * param.rep_param
+        );
+        assert!(stats.abort_count >= stats.restart_count);
+
+        // with log A = 0 every instance aborts, so the prover gives up
+        let param = Param::new(8, 2, 16, 0, 4).unwrap();
+        assert_eq!(
+            NIProver::<L1>::new(&mut rng, param).prove(),
+            Err(InternalError::Abort)
+        );
+    }
+
+    #[test]
+    fn test_proof_layout() {
+        let param = Param::new(9, 2, 2, 3, 1).unwrap();
+        let proof = NIProverMsg::<L1> {
+            param,
+            step1: [0x11; 32].into(),
+            step2: ([0x22; 32].into(), vec![[0x33; 16].into()]),
+            step3: vec![Step3Inner {
+                path: vec![[0x44; 16].into()],
+                com: [0x55; 32].into(),
+                delta_rs: (1..=9).collect(),
+                xs_tilde: vec![1, 0, 1, 1, 0, 0, 0, 0, 1],
+                t_share: 0x0102030405060708,
+            }],
+        };
+
+        let mut expected = vec![PROOF_VERSION, 1, hash_id::<L1>(), prg_id::<L1>()];
+        // n, N, M, log A and tau
+        for x in [9u32, 2, 2, 3, 1] {
+            expected.extend(x.to_le_bytes());
+        }
+        // h, then h' and the master seed path with its length
+        expected.extend([0x11; 32]);
+        expected.extend([0x22; 32]);
+        expected.extend([1, 0, 0, 0]);
+        expected.extend([0x33; 16]);
+        // the party path with its length, the commitment and delta_rs
+        expected.push(1);
+        expected.extend([0x44; 16]);
+        expected.extend([0x55; 32]);
+        for x in 1u64..=9 {
+            expected.extend(x.to_le_bytes());
+        }
+        // the packed masked witness and the share of t
+        expected.extend([0b0000_1101, 0b0000_0001]);
+        expected.extend([8, 7, 6, 5, 4, 3, 2, 1]);
+
+        assert_eq!(PROOF_VERSION, 1);
+        assert_eq!(proof.to_bytes(), expected);
+        assert_eq!(NIProverMsg::<L1>::from_bytes(&expected), Ok(proof));
+    }
+
+    #[test]
+    fn test_proof_encoding() {
+        let mut rng = ChaChaRng::from_entropy();
+        // use an ssp dimension that is not a multiple of 8 to test the padding
+        let param = Param {
+            ssp_dimension: 13,
+            ..Param::default()
+        };
+        let niprover = NIProver::<L1>::new(&mut rng, param);
+        let instance = niprover.prover.get_instance().clone();
         let proof = niprover.prove().unwrap();
 
-        // TODO verify the proof
-        let _ = proof;
+        let bytes = proof.to_bytes();
+        // the length of the master seed path depends on J
+        assert!(bytes.len() <= NIProverMsg::<L1>::max_size(param).unwrap());
+        assert_eq!(NIProverMsg::<L1>::max_size(Param::default()), Some(27700));
+        let decoded = NIProverMsg::<L1>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert_eq!(NIVerifier::verify(instance, param, &decoded), Ok(true));
+
+        // the length must match exactly
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(InternalError::BadProofLength)
+        );
+        let mut long_bytes = bytes.clone();
+        long_bytes.push(0);
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&long_bytes),
+            Err(InternalError::BadProofLength)
+        );
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&[]),
+            Err(InternalError::BadProofLength)
+        );
+
+        // a short proof that claims a huge tau is rejected before it is allocated
+        let mut huge_rep = bytes[..PROOF_HEADER_SIZE].to_vec();
+        for x in [1u32, 2, 1 << 16, 0, 1 << 16] {
+            huge_rep.extend(x.to_le_bytes());
+        }
+        huge_rep.resize(92, 0);
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&huge_rep),
+            Err(InternalError::BadProofLength)
+        );
+        huge_rep[PROOF_HEADER_SIZE + 8..PROOF_HEADER_SIZE + 12].fill(0xff);
+        huge_rep[PROOF_HEADER_SIZE + 16..PROOF_HEADER_SIZE + 20].fill(0xff);
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&huge_rep),
+            Err(InternalError::BadProofEncoding)
+        );
+
+        // unknown versions are rejected
+        let mut bad_version = bytes.clone();
+        bad_version[0] = PROOF_VERSION + 1;
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&bad_version),
+            Err(InternalError::UnsupportedProofVersion(PROOF_VERSION + 1))
+        );
+        bad_version[0] = 0;
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&bad_version),
+            Err(InternalError::UnsupportedProofVersion(0))
+        );
+
+        // the padding bits of the masked witness must be zero
+        let mut bad_padding = bytes.clone();
+        let last_packed = bytes.len() - 8 - 1;
+        bad_padding[last_packed] |= 0x80;
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&bad_padding),
+            Err(InternalError::BadProofEncoding)
+        );
+
+        // the master seed path must not be longer than the bound
+        let mseed_path = PROOF_HEADER_SIZE + PARAM_ENCODING_SIZE + 2 * digest_size::<L1>();
+        let mseed_path_len =
+            u32::from_le_bytes(bytes[mseed_path..mseed_path + 4].try_into().unwrap());
+        assert_eq!(mseed_path_len as usize, proof.step2.1.len());
+        let mut long_mseed_path = bytes.clone();
+        let max_mseed_path_len = SeedTree::<L1>::max_open_size(param.cnc_param, param.rep_param);
+        long_mseed_path[mseed_path..mseed_path + 4]
+            .copy_from_slice(&(max_mseed_path_len as u32 + 1).to_le_bytes());
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&long_mseed_path),
+            Err(InternalError::BadProofEncoding)
+        );
+
+        // the path must not be longer than the depth of the tree
+        let mut long_path = bytes.clone();
+        let first_path = mseed_path + 4 + mseed_path_len as usize * key_size::<L1>();
+        assert_eq!(long_path[first_path], 2);
+        long_path[first_path] = 3;
+        assert_eq!(
+            NIProverMsg::<L1>::from_bytes(&long_path),
+            Err(InternalError::BadProofEncoding)
+        );
+
+        // the security level must match
+        assert_eq!(
+            NIProverMsg::<L5>::from_bytes(&bytes),
+            Err(InternalError::SecurityLevelMismatch(<L1>::ID))
+        );
+
+        // so must the hash function and the PRG
+        assert_eq!(
+            NIProverMsg::<L1<Shake128>>::from_bytes(&bytes),
+            Err(InternalError::HashMismatch(bytes[2]))
+        );
+        assert_eq!(
+            NIProverMsg::<L1<Sha3_256, Shake>>::from_bytes(&bytes),
+            Err(InternalError::PrgMismatch(bytes[3]))
+        );
+
+        // serde uses the same encoding
+        let bin = bincode::serialize(&proof).unwrap();
+        assert_eq!(bincode::deserialize::<NIProverMsg>(&bin).unwrap(), proof);
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(serde_json::from_str::<NIProverMsg>(&json).unwrap(), proof);
+
+        // seeds and digests are twice as large for L5
+        let proof = NIProver::<L5>::new(&mut rng, param).prove().unwrap();
+        let bytes = proof.to_bytes();
+        assert!(bytes.len() <= NIProverMsg::<L5>::max_size(param).unwrap());
+        assert_eq!(NIProverMsg::<L5>::from_bytes(&bytes), Ok(proof));
+
+        // the paths are shorter for some parties when N is not a power of two
+        let param = Param {
+            party_count: 5,
+            ..param
+        };
+        let niprover = NIProver::<L1>::new(&mut rng, param);
+        let instance = niprover.prover.get_instance().clone();
+        let proof = niprover.prove().unwrap();
+        let bytes = proof.to_bytes();
+        assert!(bytes.len() < NIProverMsg::<L1>::max_size(param).unwrap());
+        let decoded = NIProverMsg::<L1>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert_eq!(NIVerifier::verify(instance, param, &decoded), Ok(true));
     }
 }
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    consts::PROTOCOL_VERSION,
+    digest_instance,
+    security::{hash_id, prg_id},
+    Instance, Param, SecurityParams,
+};
+
+/// The first message of the prover in the interactive protocol,
+/// the verifier replies with `VerifierMsg::Hello` before the first challenge.
+/// It does not depend on the security level,
+/// so that the verifier can read it even if the levels do not match.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Hello {
+    version: u8,
+    level: u8,
+    hash: u8,
+    prg: u8,
+    param: Param,
+    /// The digest of the public instance.
+    instance: Vec<u8>,
+}
+
+/// The reason for the verifier to reject the handshake,
+/// it carries what the verifier expects if the prover could change it.
+#[derive(Error, Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum HelloReject {
+    #[error("the verifier only supports protocol version {0}")]
+    Version(u8),
+    #[error("the verifier uses security level {level}, hash function {hash} and PRG {prg}")]
+    Backend { level: u8, hash: u8, prg: u8 },
+    #[error("the verifier uses the parameter {0:?}")]
+    Param(Param),
+    #[error("the instance is unknown to the verifier")]
+    Instance,
+}
+
+impl Hello {
+    /// Announce the instance and the parameter of the prover.
+    pub fn new<S: SecurityParams>(instance: &Instance<S>, param: Param) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            level: S::ID,
+            hash: hash_id::<S>(),
+            prg: prg_id::<S>(),
+            param,
+            instance: digest_instance(instance).to_vec(),
+        }
+    }
+
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn get_param(&self) -> Param {
+        self.param
+    }
+
+    /// Check that the prover runs the same protocol as the verifier
+    /// that verifies `instance` with `param`.
+    pub(crate) fn check<S: SecurityParams>(
+        &self,
+        instance: &Instance<S>,
+        param: Param,
+    ) -> Result<(), HelloReject> {
+        if self.version != PROTOCOL_VERSION {
+            return Err(HelloReject::Version(PROTOCOL_VERSION));
+        }
+        if (self.level, self.hash, self.prg) != (S::ID, hash_id::<S>(), prg_id::<S>()) {
+            return Err(HelloReject::Backend {
+                level: S::ID,
+                hash: hash_id::<S>(),
+                prg: prg_id::<S>(),
+            });
+        }
+        if self.param != param {
+            return Err(HelloReject::Param(param));
+        }
+        if self.instance[..] != digest_instance(instance)[..] {
+            return Err(HelloReject::Instance);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use crossbeam::channel::unbounded;
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::{
+        hash::Shake128,
+        prover::{IProver, Prover},
+        verifier::{IVerifier, Verifier},
+        InternalError, L1, L5,
+    };
+
+    #[test]
+    fn test_hello_check() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let instance = prover.get_instance();
+        let hello = Hello::new(instance, param);
+        assert_eq!(hello.check(instance, param), Ok(()));
+
+        let mut old = hello.clone();
+        old.version = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            old.check(instance, param),
+            Err(HelloReject::Version(PROTOCOL_VERSION))
+        );
+
+        assert_eq!(
+            hello.check(instance, Param::L1_FAST),
+            Err(HelloReject::Param(Param::L1_FAST))
+        );
+
+        let other = Prover::<L1>::new(&mut rng, param);
+        assert_eq!(
+            hello.check(other.get_instance(), param),
+            Err(HelloReject::Instance)
+        );
+
+        // the backend is checked before the instance, which has a different type
+        let l5 = Prover::<L5>::new(&mut rng, param);
+        assert_eq!(
+            hello.check(l5.get_instance(), param),
+            Err(HelloReject::Backend {
+                level: 5,
+                hash: hash_id::<L5>(),
+                prg: prg_id::<L5>()
+            })
+        );
+        let shake =
+            Instance::<L1<Shake128>>::new(instance.get_weights().to_vec(), instance.get_t());
+        assert!(matches!(
+            hello.check(&shake, param),
+            Err(HelloReject::Backend { level: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_handshake_rejected() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let (tx_p, rx_p) = unbounded();
+        let (tx_v, rx_v) = unbounded();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), Param::L1_FAST);
+
+        let mut iprover = IProver::from_prover(prover, tx_p, rx_v);
+        let handle = thread::spawn(move || iprover.blocking_run());
+        let mut iverifier = IVerifier::new(verifier, tx_v, rx_p);
+
+        // both sides stop before the first challenge and know the reason
+        let reason = HelloReject::Param(Param::L1_FAST);
+        assert_eq!(
+            iverifier.blocking_run(&mut rng),
+            Err(InternalError::HandshakeRejected(reason.clone()))
+        );
+        assert_eq!(
+            handle.join().unwrap(),
+            Err(InternalError::HandshakeRejected(reason))
+        );
+    }
+}
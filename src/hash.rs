@@ -0,0 +1,164 @@
+//! The hash functions used for the commitments and the transcript.
+//! The hash function is part of the security level, e.g., `L1<Shake128>`,
+//! the fixed output size functions only fit the levels with the same digest size
+//! while the XOFs fit every level.
+
+use generic_array::{
+    typenum::{U32, U64},
+    ArrayLength, GenericArray,
+};
+use sha3::digest::{Digest, ExtendableOutput, Update};
+
+/// A hash function producing digests of `D` bytes.
+pub trait ProtocolHash<D: ArrayLength<u8>>: Default + Clone + Send + Sync + 'static {
+    /// The identifier of the hash function.
+    const ID: u8;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(&mut self, data: impl AsRef<[u8]>);
+
+    fn finalize(self) -> GenericArray<u8, D>;
+}
+
+#[derive(Debug, Clone, Default)]
+/// SHA3-256, only for 32 byte digests.
+pub struct Sha3_256(sha3::Sha3_256);
+
+impl ProtocolHash<U32> for Sha3_256 {
+    const ID: u8 = 1;
+
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> GenericArray<u8, U32> {
+        Digest::finalize(self.0)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// SHA3-512, only for 64 byte digests.
+pub struct Sha3_512(sha3::Sha3_512);
+
+impl ProtocolHash<U64> for Sha3_512 {
+    const ID: u8 = 2;
+
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> GenericArray<u8, U64> {
+        Digest::finalize(self.0)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// SHAKE128, the digest is the first `D` bytes of the output stream.
+pub struct Shake128(sha3::Shake128);
+
+impl<D: ArrayLength<u8>> ProtocolHash<D> for Shake128 {
+    const ID: u8 = 3;
+
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        Update::update(&mut self.0, data.as_ref());
+    }
+
+    fn finalize(self) -> GenericArray<u8, D> {
+        let mut out = GenericArray::default();
+        self.0.finalize_xof_into(&mut out);
+        out
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// SHAKE256, the digest is the first `D` bytes of the output stream.
+pub struct Shake256(sha3::Shake256);
+
+impl<D: ArrayLength<u8>> ProtocolHash<D> for Shake256 {
+    const ID: u8 = 4;
+
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        Update::update(&mut self.0, data.as_ref());
+    }
+
+    fn finalize(self) -> GenericArray<u8, D> {
+        let mut out = GenericArray::default();
+        self.0.finalize_xof_into(&mut out);
+        out
+    }
+}
+
+#[cfg(feature = "blake3")]
+#[derive(Debug, Clone, Default)]
+/// BLAKE3 in XOF mode, the digest is the first `D` bytes of the output stream.
+pub struct Blake3(blake3::Hasher);
+
+#[cfg(feature = "blake3")]
+impl<D: ArrayLength<u8>> ProtocolHash<D> for Blake3 {
+    const ID: u8 = 5;
+
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        self.0.update(data.as_ref());
+    }
+
+    fn finalize(self) -> GenericArray<u8, D> {
+        let mut out = GenericArray::default();
+        self.0.finalize_xof().fill(&mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn digest<D: ArrayLength<u8>, H: ProtocolHash<D>>(data: &[u8]) -> String {
+        let mut hasher = H::new();
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+
+    #[test]
+    fn test_known_answers() {
+        assert_eq!(
+            digest::<U32, Sha3_256>(b""),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+        assert_eq!(
+            digest::<U64, Sha3_512>(b""),
+            "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a6\
+             15b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26"
+        );
+        assert_eq!(
+            digest::<U32, Shake128>(b""),
+            "7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef26"
+        );
+        assert_eq!(
+            digest::<U32, Shake256>(b""),
+            "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f"
+        );
+        #[cfg(feature = "blake3")]
+        assert_eq!(
+            digest::<U32, Blake3>(b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+    }
+
+    #[test]
+    fn test_xof_prefix() {
+        // a shorter digest of an XOF is a prefix of the longer one
+        let short = digest::<U32, Shake256>(b"ssith");
+        let long = digest::<U64, Shake256>(b"ssith");
+        assert_eq!(short, long[..64]);
+
+        // updating in pieces is the same as updating at once
+        let mut hasher = Shake128::default();
+        ProtocolHash::<U32>::update(&mut hasher, b"ss");
+        ProtocolHash::<U32>::update(&mut hasher, b"ith");
+        let out: GenericArray<u8, U32> = hasher.finalize();
+        assert_eq!(hex::encode(out), digest::<U32, Shake128>(b"ssith"));
+    }
+}
@@ -10,6 +10,12 @@ use serde::{de::DeserializeOwned, Serialize};
 
 const TCPSTREAM_CAP: usize = 1000;
 
+/// The largest message that is read, a larger length prefix is an error.
+/// The largest protocol message is far smaller,
+/// the limit only stops a peer from making us allocate a lot of memory.
+/// It is shared by both transports, see `async_io::Framed`.
+pub(crate) const MAX_FRAME_SIZE: usize = 1 << 24;
+
 fn try_shutdown(stream: &TcpStream) {
     match stream.shutdown(Shutdown::Both) {
         Ok(()) => (),
@@ -18,23 +24,43 @@ fn try_shutdown(stream: &TcpStream) {
 }
 
 fn read_length<R: io::Read>(reader: &mut R) -> io::Result<usize> {
-    reader.read_u64::<LittleEndian>().map(|x| x as usize)
+    check_frame_size(reader.read_u64::<LittleEndian>()?)
+}
+
+/// Check the length prefix of a frame before its buffer is allocated.
+pub(crate) fn check_frame_size(len: u64) -> io::Result<usize> {
+    match usize::try_from(len) {
+        Ok(len) if len <= MAX_FRAME_SIZE => Ok(len),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame is too large",
+        )),
+    }
 }
 
 fn write_length<W: io::Write>(writer: &mut W, len: usize) -> io::Result<()> {
     writer.write_u64::<LittleEndian>(len as u64)
 }
 
-// TODO: we could also wrap reader/writer
-/// Wrap a TcpStream into channels
-pub fn wrap_tcpstream<S, R>(
-    stream: TcpStream,
-) -> (
+fn write_msg<W: Write, T: Serialize>(writer: &mut W, msg: &T) -> io::Result<()> {
+    let data = bincode::serialize(msg).map_err(std::io::Error::other)?;
+    write_length(writer, data.len())?;
+    writer.write_all(&data)
+}
+
+/// The channels returned by `wrap_tcpstream`, i.e.,
+/// the message sender, the message receiver,
+/// the shutdown sender and the handle of the background thread.
+pub type TcpChannels<S, R> = (
     Sender<S>,
     Receiver<R>,
     Sender<()>,
     JoinHandle<Result<(), std::io::Error>>,
-)
+);
+
+// TODO: we could also wrap reader/writer
+/// Wrap a TcpStream into channels
+pub fn wrap_tcpstream<S, R>(stream: TcpStream) -> TcpChannels<S, R>
 where
     S: 'static + Sync + Send + Clone + Serialize,
     R: 'static + Sync + Send + Clone + DeserializeOwned,
@@ -55,11 +81,10 @@ where
                     reader.read_exact(&mut value_buf)?;
 
                     // TODO find a generic way to do serializatioin
-                    let msg: R = bincode::deserialize(&value_buf)
-                        .map_err(|e| std::io::Error::new(io::ErrorKind::Other, e))?;
+                    let msg: R = bincode::deserialize(&value_buf).map_err(std::io::Error::other)?;
                     match reader_s.send(msg) {
                         Ok(()) => Ok(()),
-                        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                        Err(e) => Err(io::Error::other(e)),
                     }
                 };
 
@@ -88,12 +113,8 @@ where
                         // run shutdown later when an error occurs
                         let f = || -> io::Result<()> {
                             let msg = msg_res
-                                .map_err(|e| std::io::Error::new(io::ErrorKind::Other, e))?;
-                            let data = bincode::serialize(&msg)
-                                .map_err(|e| std::io::Error::new(io::ErrorKind::Other, e))?;
-                            write_length(&mut writer, data.len())?;
-                            (&mut writer).write_all(&data)?;
-                            Ok(())
+                                .map_err(std::io::Error::other)?;
+                            write_msg(&mut writer, &msg)
                         };
 
                         match f() {
@@ -106,8 +127,13 @@ where
                         }
                     }
                     recv(shutdown_r) -> msg_res => {
+                        // the messages sent before the shutdown are still written
+                        let res = writer_r
+                            .try_iter()
+                            .try_for_each(|msg| write_msg(&mut writer, &msg));
                         try_shutdown(&writer);
-                        return msg_res.map_err(|e| std::io::Error::new(io::ErrorKind::Other, e));
+                        res?;
+                        return msg_res.map_err(std::io::Error::other);
                     }
                 }
             }
@@ -133,7 +159,7 @@ mod test {
     use serde::Deserialize;
 
     use crate::{
-        prover::IProver,
+        prover::{IProver, Prover},
         verifier::{IVerifier, Verifier},
         Param, ProverMsg, VerifierMsg,
     };
@@ -198,10 +224,38 @@ mod test {
         handle.join().unwrap().unwrap();
     }
 
+    #[test]
+    fn test_frame_size() {
+        let max = (MAX_FRAME_SIZE as u64).to_le_bytes();
+        assert_eq!(read_length(&mut &max[..]).unwrap(), MAX_FRAME_SIZE);
+        let too_large = (MAX_FRAME_SIZE as u64 + 1).to_le_bytes();
+        assert_eq!(
+            read_length(&mut &too_large[..]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // a huge length prefix stops the reader before anything is allocated
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (_sender, receiver, shutdown_sender, handle) =
+            wrap_tcpstream::<DummyMsg, DummyMsg>(stream);
+        peer.write_all(&u64::MAX.to_le_bytes()).unwrap();
+        assert!(receiver.recv().is_err());
+        shutdown_sender.send(()).unwrap();
+        assert_eq!(
+            handle.join().unwrap().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
     #[test]
     fn test_tcp_proof() {
         const ADDR: &str = "127.0.0.1:11112";
         let param = Param::default();
+        let mut rng = ChaChaRng::from_entropy();
+        let prover = Prover::new(&mut rng, param);
+        let instance = prover.get_instance().clone();
 
         // we need some synchronization for the test to run correctly,
         // i.e., client only connects to server when the server is ready
@@ -214,7 +268,7 @@ mod test {
             let (tx, rx, shutdown_sender, handle) =
                 wrap_tcpstream::<VerifierMsg, ProverMsg>(stream);
 
-            let mut iverifier = IVerifier::new(Verifier::new(param), tx, rx);
+            let mut iverifier = IVerifier::new(Verifier::new(instance, param), tx, rx);
             let mut rng = ChaChaRng::from_entropy();
             let output = iverifier.blocking_run(&mut rng).unwrap();
             shutdown_sender.send(()).unwrap();
@@ -228,10 +282,9 @@ mod test {
         // test the wrapper, first receive the first message from server
         let (tx, rx, shutdown_sender, handle) = wrap_tcpstream::<ProverMsg, VerifierMsg>(stream);
 
-        let mut rng = ChaChaRng::from_entropy();
-        let mut iprover = IProver::new(&mut rng, param, tx, rx);
+        let mut iprover = IProver::from_prover(prover, tx, rx);
 
-        iprover.blocking_run().unwrap();
+        assert!(iprover.blocking_run().unwrap());
         shutdown_sender.send(()).unwrap();
         handle.join().unwrap().unwrap();
 
@@ -1,72 +1,184 @@
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod client;
 mod consts;
 mod errors;
 pub mod fiat_shamir;
+pub mod handshake;
+pub mod hash;
 pub mod io;
+mod param;
+pub mod prg;
 mod primitives;
 pub mod prover;
+mod security;
+mod seed_tree;
+pub mod server;
+pub mod session;
+pub mod signature;
 pub mod verifier;
 
 use consts::*;
-use errors::*;
+pub use errors::InternalError;
+use generic_array::typenum::Unsigned;
+pub use hash::ProtocolHash;
+pub use param::{Analysis, Param, ParamBuilder};
+pub use prg::Prg;
+use primitives::{iv_from_label, prg_bin, prg_double, prg_u64};
 use rand_core::{CryptoRng, RngCore};
+use security::hex_array;
+pub use security::{Block, DigestOutput, Key, OpeningArray, SecurityParams, L1, L5};
 use serde::{Deserialize, Serialize};
-use sha3::{Digest, Sha3_256};
-
-#[derive(Debug, Copy, Clone, Serialize)]
-/// Parameter for the subset sum MPCitH protocol.
-pub struct Param {
-    /// Dimension of the SSP (n)
-    ssp_dimension: usize,
-    /// Number of parties (N)
-    party_count: usize,
-    /// Parameter for cut and choose (M)
-    cnc_param: usize,
-    /// Parameter for abort in bits, i.e., log A
-    abort_param: usize,
-    /// The number of reps (tau)
-    rep_param: usize,
-}
-
-impl Default for Param {
-    fn default() -> Self {
-        Param {
-            ssp_dimension: 128,
-            party_count: 4,
-            cnc_param: 100,
-            abort_param: 14,
-            rep_param: 24,
-        }
-    }
-}
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<u8>")]
 // TODO impl deref
 /// The witness (solution) to the subset sum problem.
 pub struct Witness(Vec<u8>);
 
 impl Witness {
+    /// Create a witness from a vector of bits,
+    /// where every bit is represented by a u8.
+    /// An error is returned if any entry is not 0 or 1.
+    pub fn from_bits(bits: Vec<u8>) -> Result<Self, InternalError> {
+        if bits.iter().any(|b| *b > 1) {
+            return Err(InternalError::BadWitnessValue);
+        }
+        Ok(Self(bits))
+    }
+
+    pub fn get_bits(&self) -> &[u8] {
+        &self.0
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<Vec<u8>> for Witness {
+    type Error = InternalError;
+
+    fn try_from(bits: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::from_bits(bits)
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "InstanceRepr<S>", try_from = "InstanceRepr<S>", bound = "")]
 /// The instance of the subset sum problem.
-pub struct Instance {
+/// The weights are either given explicitly or expanded from a public seed,
+/// in the latter case only the seed is serialized and hashed.
+pub struct Instance<S: SecurityParams = L1> {
     weights: Vec<u64>,
     t: u64,
+    weights_seed: Option<Key<S>>,
 }
 
-fn sanity_check(witness: &Witness, instance: &Instance, param: Param) -> Result<(), InternalError> {
+impl<S: SecurityParams> Instance<S> {
+    /// Create an instance from the weights and the target sum `t`.
+    /// The sum is computed modulo 2^64.
+    pub fn new(weights: Vec<u64>, t: u64) -> Self {
+        Self {
+            weights,
+            t,
+            weights_seed: None,
+        }
+    }
+
+    /// Create an instance where the `ssp_dimension` weights
+    /// are expanded from the public seed `weights_seed`.
+    /// The weights are expanded eagerly, so a deserialized instance
+    /// is rejected if its dimension is larger than any valid `Param` allows.
+    pub fn from_weights_seed(weights_seed: Key<S>, t: u64, ssp_dimension: usize) -> Self {
+        Self {
+            weights: weights_from_seed::<S>(&weights_seed, ssp_dimension),
+            t,
+            weights_seed: Some(weights_seed),
+        }
+    }
+
+    pub fn get_weights(&self) -> &[u64] {
+        &self.weights
+    }
+
+    pub fn get_t(&self) -> u64 {
+        self.t
+    }
+
+    pub fn get_weights_seed(&self) -> Option<&Key<S>> {
+        self.weights_seed.as_ref()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+/// The serialized form of `Instance`.
+enum InstanceRepr<S: SecurityParams> {
+    Weights {
+        weights: Vec<u64>,
+        t: u64,
+    },
+    Seed {
+        #[serde(with = "hex_array")]
+        weights_seed: Key<S>,
+        ssp_dimension: usize,
+        t: u64,
+    },
+}
+
+impl<S: SecurityParams> From<Instance<S>> for InstanceRepr<S> {
+    fn from(instance: Instance<S>) -> Self {
+        match instance.weights_seed {
+            Some(weights_seed) => InstanceRepr::Seed {
+                weights_seed,
+                ssp_dimension: instance.weights.len(),
+                t: instance.t,
+            },
+            None => InstanceRepr::Weights {
+                weights: instance.weights,
+                t: instance.t,
+            },
+        }
+    }
+}
+
+impl<S: SecurityParams> TryFrom<InstanceRepr<S>> for Instance<S> {
+    type Error = InternalError;
+
+    fn try_from(repr: InstanceRepr<S>) -> Result<Self, Self::Error> {
+        match repr {
+            InstanceRepr::Weights { weights, t } => Ok(Instance::new(weights, t)),
+            InstanceRepr::Seed {
+                weights_seed,
+                ssp_dimension,
+                t,
+            } => {
+                if ssp_dimension > MAX_SSP_DIMENSION {
+                    return Err(InternalError::BadSspDimension);
+                }
+                Ok(Instance::from_weights_seed(weights_seed, t, ssp_dimension))
+            }
+        }
+    }
+}
+
+fn sanity_check<S: SecurityParams>(
+    witness: &Witness,
+    instance: &Instance<S>,
+    param: Param,
+) -> Result<(), InternalError> {
     if witness.len() != param.ssp_dimension {
         return Err(InternalError::BadWitnessLength);
     }
     if instance.weights.len() != param.ssp_dimension {
         return Err(InternalError::BadInstanceLength);
     }
-    if param.abort_param >= 64 {
-        return Err(InternalError::BadAbortParam);
-    }
+    param.validate()?;
 
     // recompute the inner product
     let t: u64 = witness
@@ -81,55 +193,158 @@ fn sanity_check(witness: &Witness, instance: &Instance, param: Param) -> Result<
     Ok(())
 }
 
-fn new_witness_instance<R: RngCore + CryptoRng>(rng: &mut R, param: Param) -> (Witness, Instance) {
-    // TODO: this is a bit redundant since we only need one bit from a byte
-    let mut w_vec = vec![0u8; param.ssp_dimension];
-    rng.fill_bytes(&mut w_vec);
-    w_vec.iter_mut().for_each(|w| {
-        *w = *w % 2;
-    });
+fn new_witness_instance<S: SecurityParams, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    param: Param,
+) -> (Witness, Instance<S>) {
+    let mut seed = Key::<S>::default();
+    rng.fill_bytes(&mut seed);
+    witness_instance_from_seed(&seed, param)
+}
 
-    let mut weights = vec![0u64; param.ssp_dimension];
-    for x in &mut weights {
-        *x = rng.next_u64();
-    }
+/// Deterministically expand a secret `seed` into a witness-instance pair.
+/// The weights are expanded from a public seed that is derived from `seed`,
+/// so the instance is stored as the public seed and t.
+pub fn witness_instance_from_seed<S: SecurityParams>(
+    seed: &Key<S>,
+    param: Param,
+) -> (Witness, Instance<S>) {
+    let (weights_seed, witness_seed) = prg_double::<S>(seed, &iv_from_label::<S>(&IV_KEYGEN));
+    let w_vec = prg_bin::<S>(
+        &witness_seed,
+        &iv_from_label::<S>(&IV_KEYGEN_WITNESS),
+        param.ssp_dimension,
+    );
+    let mut instance = Instance::from_weights_seed(weights_seed, 0, param.ssp_dimension);
 
-    let t: u64 = weights
+    instance.t = instance
+        .weights
         .iter()
         .zip(&w_vec)
         .map(|(weight, witness)| weight * (*witness as u64))
         .fold(0u64, |acc, s| acc.wrapping_add(s));
 
-    (Witness(w_vec), Instance { weights, t })
+    (Witness(w_vec), instance)
 }
 
-fn hash_witness_instance(witness: &Witness, instance: &Instance) -> [u8; BLOCK_SIZE] {
-    let mut hasher = Sha3_256::new();
-    hasher.update(PREFIX_WITNESS);
-    hasher.update(&witness.len().to_le_bytes());
-    hasher.update(&witness.0);
+/// Expand the public weights of an instance from a public seed.
+fn weights_from_seed<S: SecurityParams>(weights_seed: &Key<S>, ssp_dimension: usize) -> Vec<u64> {
+    prg_u64::<S>(
+        weights_seed,
+        &iv_from_label::<S>(&IV_KEYGEN_WEIGHTS),
+        ssp_dimension,
+    )
+}
 
-    hasher.update(PREFIX_INSTANCE);
-    hasher.update(&instance.weights.len().to_le_bytes());
-    for weight in &instance.weights {
-        hasher.update(&weight.to_le_bytes());
+/// Hash the instance, the output is used as the IV of all the PRGs.
+/// Only public information is hashed since the verifier needs to recompute it.
+fn hash_instance<S: SecurityParams>(instance: &Instance<S>) -> Block<S> {
+    let result = digest_instance(instance);
+    Block::<S>::clone_from_slice(&result[..S::BlockSize::USIZE])
+}
+
+/// The full digest of the instance, `hash_instance` truncates it to a block.
+fn digest_instance<S: SecurityParams>(instance: &Instance<S>) -> DigestOutput<S> {
+    let mut hasher = S::Hash::new();
+    update_instance(&mut hasher, instance);
+    hasher.finalize()
+}
+
+/// Absorb the instance into `hasher`,
+/// if the weights are expanded from a seed then only the seed is absorbed.
+fn update_instance<S: SecurityParams>(hasher: &mut S::Hash, instance: &Instance<S>) {
+    match &instance.weights_seed {
+        Some(weights_seed) => {
+            hasher.update(PREFIX_INSTANCE_SEED);
+            hasher.update(weights_seed);
+            hasher.update(instance.weights.len().to_le_bytes());
+        }
+        None => {
+            hasher.update(PREFIX_INSTANCE);
+            hasher.update(instance.weights.len().to_le_bytes());
+            for weight in &instance.weights {
+                hasher.update(weight.to_le_bytes());
+            }
+        }
     }
-    hasher.update(&instance.t.to_le_bytes());
+    hasher.update(instance.t.to_le_bytes());
+}
+
+/// Expand the seed of a party into its share of r.
+fn r_share_from_seed<S: SecurityParams>(seed: &Key<S>, iv: &Block<S>, param: Param) -> Vec<u64> {
+    prg_u64::<S>(seed, iv, param.ssp_dimension)
+        .iter()
+        .map(|x| x % (1 << param.abort_param as u64))
+        .collect()
+}
+
+/// Compute the share of `t` held by one party in one C&C instance,
+/// using the relation x = x_tilde + (1 - 2 * x_tilde) * r.
+/// The first party additionally holds the public values,
+/// i.e., `delta_rs` and `xs_tilde`, so that the shares of all parties sum to `t`.
+fn compute_t_share<S: SecurityParams>(
+    instance: &Instance<S>,
+    xs_tilde: &[u8],
+    r_share: &[u64],
+    delta_rs: Option<&[u64]>,
+) -> u64 {
+    let x_share = xs_tilde
+        .iter()
+        .zip(r_share)
+        .enumerate()
+        .map(|(j, (x_tilde, r_share))| {
+            let x_tilde = u64::from(*x_tilde);
+            let (r_share, constant) = match delta_rs {
+                Some(delta_rs) => (r_share.wrapping_add(delta_rs[j]), x_tilde),
+                None => (*r_share, 0),
+            };
+            1u64.wrapping_sub(x_tilde.wrapping_mul(2))
+                .wrapping_mul(r_share)
+                .wrapping_add(constant)
+        });
+    instance
+        .weights
+        .iter()
+        .zip(x_share)
+        .map(|(w, x)| x.wrapping_mul(*w))
+        .fold(0, |acc, s| acc.wrapping_add(s))
+}
 
-    let result = hasher.finalize();
-    result.as_slice()[..BLOCK_SIZE].try_into().unwrap()
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(bound = "")]
+/// The response of the prover in the third step for one repetition in J.
+/// All parties except the unopened party ell are opened.
+pub struct Step3Inner<S: SecurityParams = L1> {
+    /// The nodes of the seed tree that reveal the leaves of all parties except ell,
+    /// every leaf is expanded into the seed and the opening of one party.
+    path: Vec<Key<S>>,
+    /// The commitment of the unopened party.
+    com: DigestOutput<S>,
+    /// The public difference between r and the sum of its shares.
+    delta_rs: Vec<u64>,
+    /// The masked witness.
+    xs_tilde: Vec<u8>,
+    /// The broadcast share of t of the unopened party.
+    t_share: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
-pub enum ProverMsg {
-    Step1([u8; DIGEST_SIZE]),
-    Step2(([u8; DIGEST_SIZE], Vec<[u8; BLOCK_SIZE]>)),
+#[serde(bound = "")]
+pub enum ProverMsg<S: SecurityParams = L1> {
+    Hello(handshake::Hello),
+    Step1(DigestOutput<S>),
+    Step2((DigestOutput<S>, Vec<Key<S>>)),
+    Step3(Vec<Step3Inner<S>>),
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum VerifierMsg {
+    /// Whether the verifier accepts the handshake, and the reason if it does not.
+    Hello(Result<(), handshake::HelloReject>),
     Step1(Vec<usize>),
     Step2(Vec<usize>),
+    /// Whether the proof is accepted, sent after the last prover message.
+    Verdict(bool),
 }
 
 #[cfg(test)]
@@ -142,14 +357,14 @@ mod tests {
     fn test_sanity_check() {
         let mut rng = ChaChaRng::from_entropy();
         let param = Param::default();
-        let (witness, instance) = new_witness_instance(&mut rng, param);
+        let (witness, instance) = new_witness_instance::<L1, _>(&mut rng, param);
         assert_eq!(sanity_check(&witness, &instance, param), Ok(()));
 
         let short_param = Param {
             ssp_dimension: 9,
             ..param
         };
-        let (short_witness, short_instance) = new_witness_instance(&mut rng, short_param);
+        let (short_witness, short_instance) = new_witness_instance::<L1, _>(&mut rng, short_param);
         assert_eq!(
             sanity_check(&short_witness, &short_instance, short_param),
             Ok(())
@@ -172,4 +387,103 @@ mod tests {
             Err(InternalError::BadAbortParam)
         );
     }
+
+    #[test]
+    fn test_witness_instance_from_seed() {
+        let param = Param::default();
+        let seed = Key::<L1>::from([7u8; 16]);
+        let (witness, instance) = witness_instance_from_seed(&seed, param);
+        assert_eq!(sanity_check(&witness, &instance, param), Ok(()));
+        let weights_seed = *instance.get_weights_seed().unwrap();
+        assert_eq!(
+            Instance::from_weights_seed(weights_seed, instance.t, param.ssp_dimension),
+            instance
+        );
+
+        // the same seed gives the same pair
+        let (witness2, instance2) = witness_instance_from_seed(&seed, param);
+        assert_eq!(witness.get_bits(), witness2.get_bits());
+        assert_eq!(instance, instance2);
+
+        // a different seed gives a different pair
+        let (witness3, instance3) = witness_instance_from_seed::<L1>(&[8u8; 16].into(), param);
+        assert_ne!(witness.get_bits(), witness3.get_bits());
+        assert_ne!(instance, instance3);
+
+        // the weights seed must not be the same as the secret seed
+        assert_ne!(seed, weights_seed);
+
+        // the seeds are twice as large at the 256-bit security level
+        let (witness, instance) = witness_instance_from_seed::<L5>(&Key::<L5>::default(), param);
+        assert_eq!(sanity_check(&witness, &instance, param), Ok(()));
+        assert_eq!(instance.get_weights_seed().unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_compact_instance() {
+        let param = Param::default();
+        let (_, instance) = witness_instance_from_seed::<L1>(&[7u8; 16].into(), param);
+        let explicit = Instance::new(instance.weights.clone(), instance.t);
+
+        // only the seed, the dimension and t are serialized
+        let compact_bytes = bincode::serialize(&instance).unwrap();
+        let explicit_bytes = bincode::serialize(&explicit).unwrap();
+        assert!(compact_bytes.len() < 64);
+        assert!(explicit_bytes.len() > 8 * param.ssp_dimension);
+        assert_eq!(
+            bincode::deserialize::<Instance>(&compact_bytes).unwrap(),
+            instance
+        );
+        assert_eq!(
+            bincode::deserialize::<Instance>(&explicit_bytes).unwrap(),
+            explicit
+        );
+        let json = serde_json::to_string(&instance).unwrap();
+        assert_eq!(serde_json::from_str::<Instance>(&json).unwrap(), instance);
+
+        // a huge dimension is rejected before any weight is expanded
+        let huge_json = json.replace(
+            &format!("\"ssp_dimension\":{}", param.ssp_dimension),
+            "\"ssp_dimension\":1000000000000000",
+        );
+        assert_ne!(huge_json, json);
+        let err = serde_json::from_str::<Instance>(&huge_json).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&InternalError::BadSspDimension.to_string()));
+
+        // the compact form is hashed, so the two forms have different hashes
+        assert_ne!(hash_instance(&instance), hash_instance(&explicit));
+    }
+
+    #[test]
+    fn test_witness_instance_from_data() {
+        let param = Param {
+            ssp_dimension: 4,
+            ..Param::default()
+        };
+        let witness = Witness::from_bits(vec![1, 0, 1, 1]).unwrap();
+        let instance = Instance::<L1>::new(vec![3, 5, 7, u64::MAX], 9);
+        assert_eq!(sanity_check(&witness, &instance, param), Ok(()));
+
+        let bad_instance = Instance::<L1>::new(vec![3, 5, 7, u64::MAX], 10);
+        assert_eq!(
+            sanity_check(&witness, &bad_instance, param),
+            Err(InternalError::BadWitnessOrInstance)
+        );
+
+        // only 0 and 1 are valid entries
+        assert_eq!(
+            Witness::from_bits(vec![1, 0, 2, 1]).unwrap_err(),
+            InternalError::BadWitnessValue
+        );
+        assert!(serde_json::from_str::<Witness>("[1,0,2,1]").is_err());
+
+        let json = serde_json::to_string(&witness).unwrap();
+        let decoded: Witness = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.get_bits(), witness.get_bits());
+
+        let json = serde_json::to_string(&instance).unwrap();
+        assert_eq!(serde_json::from_str::<Instance>(&json).unwrap(), instance);
+    }
 }
@@ -0,0 +1,571 @@
+use crate::consts::{
+    MAX_CNC_PARAM, MAX_PARTY_COUNT, MAX_RESTARTS, MAX_SSP_DIMENSION, RESTART_FAILURE_BITS,
+};
+use crate::errors::InternalError;
+use crate::fiat_shamir::NIProverMsg;
+use crate::security::SecurityParams;
+use serde::{Deserialize, Serialize};
+
+/// The party counts considered by `Param::search`.
+const SEARCH_PARTY_COUNTS: [usize; 8] = [2, 4, 8, 16, 32, 64, 128, 256];
+/// The largest number of repetitions considered by `Param::search`.
+const SEARCH_MAX_REP: usize = 256;
+/// The largest cut and choose parameter considered by `Param::search`.
+const SEARCH_MAX_CNC: usize = 4096;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The analysis of a parameter, see `Param::analyze`.
+pub struct Analysis {
+    /// The soundness of the interactive protocol in bits,
+    /// i.e., -log2 of the cheating probability.
+    pub soundness_bits: f64,
+    /// The soundness of the non-interactive proof in bits,
+    /// i.e., log2 of the number of hash calls to forge a proof.
+    pub fs_soundness_bits: f64,
+    /// The probability that one cut and choose instance aborts.
+    pub abort_probability: f64,
+    /// The maximum size of a non-interactive proof in bytes.
+    pub proof_size: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ParamRepr")]
+/// Parameter for the subset sum MPCitH protocol.
+/// A parameter can only be created through `Param::new`, `ParamBuilder`,
+/// the presets or deserialization, all of them validate the fields.
+pub struct Param {
+    /// Dimension of the SSP (n)
+    pub(crate) ssp_dimension: usize,
+    /// Number of parties (N)
+    pub(crate) party_count: usize,
+    /// Parameter for cut and choose (M)
+    pub(crate) cnc_param: usize,
+    /// Parameter for abort in bits, i.e., log A
+    pub(crate) abort_param: usize,
+    /// The number of reps (tau)
+    pub(crate) rep_param: usize,
+}
+
+/// The default parameter is only meant for testing,
+/// it gives 44 bits of soundness and only about 27 bits against a forger
+/// of non-interactive proofs, see the presets for 128 bits.
+impl Default for Param {
+    fn default() -> Self {
+        Param {
+            ssp_dimension: 128,
+            party_count: 4,
+            cnc_param: 100,
+            abort_param: 14,
+            rep_param: 24,
+        }
+    }
+}
+
+impl Param {
+    /// 128 bits of soundness with few parties,
+    /// so it needs far fewer PRG and hash calls than `L1_SHORT`.
+    /// A forger needs 2^128 hash calls, see `Param::fs_soundness_bits`,
+    /// the interactive cheating probability is 2^-169.2
+    /// and the proof is at most 103362 bytes.
+    /// log A does not change the proof size,
+    /// it is large enough that a run restarts with probability 1%,
+    /// see `Param::restart_probability`.
+    pub const L1_FAST: Param = Param {
+        ssp_dimension: 128,
+        party_count: 4,
+        cnc_param: 3505,
+        abort_param: 20,
+        rep_param: 86,
+    };
+
+    /// 128 bits of soundness with the smallest proof size.
+    /// A forger needs 2^128 hash calls, the interactive cheating probability
+    /// is 2^-245.6 and the proof is at most 43653 bytes.
+    /// log A is larger than in `L1_FAST`, so a run restarts with probability 0.03%.
+    pub const L1_SHORT: Param = Param {
+        ssp_dimension: 128,
+        party_count: 256,
+        cnc_param: 3317,
+        abort_param: 24,
+        rep_param: 33,
+    };
+
+    /// 256 bits of soundness with few parties, meant for the `L5` security level.
+    /// A forger needs 2^256 hash calls, the interactive cheating probability
+    /// is 2^-361 and the proof is at most 240034 bytes.
+    pub const L5_FAST: Param = Param {
+        ssp_dimension: 128,
+        party_count: 4,
+        cnc_param: 3890,
+        abort_param: 20,
+        rep_param: 182,
+    };
+
+    /// 256 bits of soundness with the smallest proof size,
+    /// meant for the `L5` security level.
+    /// A forger needs 2^256 hash calls, the interactive cheating probability
+    /// is 2^-491.9 and the proof is at most 112036 bytes.
+    pub const L5_SHORT: Param = Param {
+        ssp_dimension: 128,
+        party_count: 256,
+        cnc_param: 3855,
+        abort_param: 24,
+        rep_param: 72,
+    };
+
+    /// Create a new parameter and validate it.
+    pub fn new(
+        ssp_dimension: usize,
+        party_count: usize,
+        cnc_param: usize,
+        abort_param: usize,
+        rep_param: usize,
+    ) -> Result<Self, InternalError> {
+        let param = Param {
+            ssp_dimension,
+            party_count,
+            cnc_param,
+            abort_param,
+            rep_param,
+        };
+        param.validate()?;
+        Ok(param)
+    }
+
+    /// Create a builder that starts from the default parameter.
+    pub fn builder() -> ParamBuilder {
+        ParamBuilder::default()
+    }
+
+    /// Check that the parameter is consistent.
+    /// The party count does not need to be a power of two
+    /// since the seed tree can be unbalanced.
+    /// n, N and M are at most 2^16, so a decoded parameter
+    /// cannot make us expand huge seed trees, and tau is at most M.
+    pub fn validate(&self) -> Result<(), InternalError> {
+        if self.ssp_dimension == 0 || self.ssp_dimension > MAX_SSP_DIMENSION {
+            return Err(InternalError::BadSspDimension);
+        }
+        if self.party_count < 2 || self.party_count > MAX_PARTY_COUNT {
+            return Err(InternalError::BadPartyCount);
+        }
+        if self.cnc_param > MAX_CNC_PARAM {
+            return Err(InternalError::BadCncParam);
+        }
+        if self.rep_param == 0 || self.rep_param > self.cnc_param {
+            return Err(InternalError::BadRepParam);
+        }
+        if self.abort_param >= 64 {
+            return Err(InternalError::BadAbortParam);
+        }
+        Ok(())
+    }
+
+    /// The soundness of the interactive protocol in bits,
+    /// i.e., -log2 of the cheating probability.
+    /// Non-interactive proofs are weaker, see `Param::fs_soundness_bits`.
+    ///
+    /// A cheating prover prepares c bad instances out of M, e.g., where r is not binary.
+    /// It wins if all of them are in J, which happens with probability
+    /// C(M - c, tau - c) / C(M, tau), and if it guesses the unopened party
+    /// of the remaining tau - c repetitions, each with probability 1 / N.
+    /// The cheating probability of one run is the maximum over c of
+    ///
+    /// C(M - c, tau - c) / C(M, tau) * N^-(tau - c).
+    ///
+    /// The verifier sends fresh J and L every time the prover restarts,
+    /// and it cannot tell an honest abort from a cheating prover that wants other challenges,
+    /// so the cheating probability is multiplied by `Param::max_restarts` + 1.
+    pub fn soundness_bits(&self) -> f64 {
+        let log_n = (self.party_count as f64).log2();
+        let best = self
+            .log_cnc_probabilities()
+            .enumerate()
+            .map(|(c, log_cnc)| log_cnc - (self.rep_param - c) as f64 * log_n)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let log_runs = ((self.max_restarts() + 1) as f64).log2();
+        (-best - log_runs).max(0.0)
+    }
+
+    /// The soundness of the non-interactive proof in bits,
+    /// i.e., log2 of the number of hash calls to forge a proof.
+    ///
+    /// The challenges J and L are computed from separate hashes,
+    /// so a forger grinds them one after the other as in the attack of
+    /// Kales and Zaverucha on five-round Fiat-Shamir signatures.
+    /// It first tries new first messages until the c bad instances are in J,
+    /// then tries new second messages until it guesses the unopened parties
+    /// of the remaining tau - c repetitions. The cost is the minimum over c of
+    ///
+    /// C(M, tau) / C(M - c, tau - c) + N^(tau - c),
+    ///
+    /// which is far below 2^soundness_bits, e.g., about 2^27 for the default parameter.
+    pub fn fs_soundness_bits(&self) -> f64 {
+        let log_n = (self.party_count as f64).log2();
+        self.log_cnc_probabilities()
+            .enumerate()
+            .map(|(c, log_cnc)| {
+                // log2(2^a + 2^b) without overflow
+                let (a, b) = (-log_cnc, (self.rep_param - c) as f64 * log_n);
+                a.max(b) + (-(a - b).abs()).exp2().ln_1p() / std::f64::consts::LN_2
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// log2 of C(M - c, tau - c) / C(M, tau) for c in [0, tau],
+    /// i.e., the probability that c bad instances are all in J.
+    /// It is the product of (tau - i) / (M - i) for i in [0, c).
+    fn log_cnc_probabilities(&self) -> impl Iterator<Item = f64> {
+        let m = self.cnc_param as f64;
+        let tau = self.rep_param as f64;
+        (0..=self.rep_param).scan(0f64, move |log_cnc, c| {
+            let out = *log_cnc;
+            *log_cnc += ((tau - c as f64) / (m - c as f64)).log2();
+            Some(out)
+        })
+    }
+
+    /// The probability that one cut and choose instance in J aborts given L.
+    ///
+    /// Given delta_r and the N - 1 opened shares of r, the verifier learns r - s,
+    /// where s is the share of the unopened party, which reveals r
+    /// if s is at the boundary of [0, A), i.e., it is 0 when r = 1
+    /// or A - 1 when r = 0. This happens with probability 1 / A for
+    /// each of the n coordinates of s, the opened shares are not checked
+    /// since an abort on them would tell the verifier about r.
+    pub fn abort_probability(&self) -> f64 {
+        let exponent = self.ssp_dimension as f64;
+        let p = (-(self.abort_param as f64)).exp2();
+        -(exponent * (-p).ln_1p()).exp_m1()
+    }
+
+    /// The probability that one of the tau instances in J aborts given L,
+    /// so that the prover has to restart.
+    pub fn restart_probability(&self) -> f64 {
+        let p = self.abort_probability();
+        -((self.rep_param as f64) * (-p).ln_1p()).exp_m1()
+    }
+
+    /// The number of restarts that the prover and the verifier allow in one session.
+    /// It is the smallest number such that an honest prover runs out of restarts
+    /// with probability at most 2^-40, but at most 256,
+    /// since every restart costs soundness, see `Param::soundness_bits`.
+    pub fn max_restarts(&self) -> usize {
+        let log_p = self.restart_probability().log2();
+        if log_p >= 0.0 {
+            return MAX_RESTARTS;
+        }
+        // log_p is -inf if the instances never abort
+        ((RESTART_FAILURE_BITS / -log_p).ceil() as usize)
+            .saturating_sub(1)
+            .min(MAX_RESTARTS)
+    }
+
+    /// Analyze the soundness, the abort probability
+    /// and the proof size at the security level `S`.
+    pub fn analyze<S: SecurityParams>(&self) -> Analysis {
+        Analysis {
+            soundness_bits: self.soundness_bits(),
+            fs_soundness_bits: self.fs_soundness_bits(),
+            abort_probability: self.abort_probability(),
+            proof_size: NIProverMsg::<S>::max_size(*self).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Find the parameter with the smallest proof size that reaches
+    /// `target_bits` of non-interactive soundness, see `Param::fs_soundness_bits`,
+    /// for the given SSP dimension and abort parameter,
+    /// the proof size is computed at the security level `S`.
+    /// The search considers N as a power of two up to 256,
+    /// tau up to 256 and M up to 4096.
+    /// None is returned if no such parameter exists.
+    pub fn search<S: SecurityParams>(
+        ssp_dimension: usize,
+        abort_param: usize,
+        target_bits: f64,
+    ) -> Option<Param> {
+        let mut best: Option<(usize, Param)> = None;
+        for party_count in SEARCH_PARTY_COUNTS {
+            for rep_param in 1..=SEARCH_MAX_REP {
+                // the cost of a forgery increases with M,
+                // so binary search for the smallest M that reaches the target
+                let make = |cnc_param| Param {
+                    ssp_dimension,
+                    party_count,
+                    cnc_param,
+                    abort_param,
+                    rep_param,
+                };
+                if make(SEARCH_MAX_CNC).fs_soundness_bits() < target_bits {
+                    continue;
+                }
+                let (mut lo, mut hi) = (rep_param, SEARCH_MAX_CNC);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if make(mid).fs_soundness_bits() >= target_bits {
+                        hi = mid;
+                    } else {
+                        lo = mid + 1;
+                    }
+                }
+
+                let param = make(lo);
+                if param.validate().is_err() {
+                    continue;
+                }
+                let size = match NIProverMsg::<S>::max_size(param) {
+                    Some(size) => size,
+                    None => continue,
+                };
+                if best.is_none_or(|(best_size, _)| size < best_size) {
+                    best = Some((size, param));
+                }
+            }
+        }
+        best.map(|(_, param)| param)
+    }
+
+    pub fn get_ssp_dimension(&self) -> usize {
+        self.ssp_dimension
+    }
+
+    pub fn get_party_count(&self) -> usize {
+        self.party_count
+    }
+
+    pub fn get_cnc_param(&self) -> usize {
+        self.cnc_param
+    }
+
+    pub fn get_abort_param(&self) -> usize {
+        self.abort_param
+    }
+
+    pub fn get_rep_param(&self) -> usize {
+        self.rep_param
+    }
+}
+
+#[derive(Deserialize)]
+/// The unvalidated form of `Param` used for deserialization.
+struct ParamRepr {
+    ssp_dimension: usize,
+    party_count: usize,
+    cnc_param: usize,
+    abort_param: usize,
+    rep_param: usize,
+}
+
+impl TryFrom<ParamRepr> for Param {
+    type Error = InternalError;
+
+    fn try_from(repr: ParamRepr) -> Result<Self, Self::Error> {
+        Param::new(
+            repr.ssp_dimension,
+            repr.party_count,
+            repr.cnc_param,
+            repr.abort_param,
+            repr.rep_param,
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// A builder for `Param`, the fields that are not set
+/// are taken from the default parameter.
+pub struct ParamBuilder {
+    param: Param,
+}
+
+impl ParamBuilder {
+    pub fn ssp_dimension(mut self, ssp_dimension: usize) -> Self {
+        self.param.ssp_dimension = ssp_dimension;
+        self
+    }
+
+    pub fn party_count(mut self, party_count: usize) -> Self {
+        self.param.party_count = party_count;
+        self
+    }
+
+    pub fn cnc_param(mut self, cnc_param: usize) -> Self {
+        self.param.cnc_param = cnc_param;
+        self
+    }
+
+    pub fn abort_param(mut self, abort_param: usize) -> Self {
+        self.param.abort_param = abort_param;
+        self
+    }
+
+    pub fn rep_param(mut self, rep_param: usize) -> Self {
+        self.param.rep_param = rep_param;
+        self
+    }
+
+    /// Validate and output the parameter.
+    pub fn build(self) -> Result<Param, InternalError> {
+        self.param.validate()?;
+        Ok(self.param)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::{L1, L5};
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Param::default().validate(), Ok(()));
+        assert_eq!(Param::L1_FAST.validate(), Ok(()));
+        assert_eq!(Param::L1_SHORT.validate(), Ok(()));
+        assert_eq!(Param::L5_FAST.validate(), Ok(()));
+        assert_eq!(Param::L5_SHORT.validate(), Ok(()));
+
+        let param = Param::builder()
+            .ssp_dimension(64)
+            .party_count(8)
+            .cnc_param(10)
+            .rep_param(10)
+            .abort_param(20)
+            .build()
+            .unwrap();
+        assert_eq!(param, Param::new(64, 8, 10, 20, 10).unwrap());
+
+        assert_eq!(
+            Param::builder().ssp_dimension(0).build(),
+            Err(InternalError::BadSspDimension)
+        );
+        assert_eq!(
+            Param::builder()
+                .ssp_dimension(MAX_SSP_DIMENSION + 1)
+                .build(),
+            Err(InternalError::BadSspDimension)
+        );
+        assert_eq!(
+            Param::builder().party_count(1).build(),
+            Err(InternalError::BadPartyCount)
+        );
+        assert_eq!(
+            Param::builder().party_count(MAX_PARTY_COUNT + 1).build(),
+            Err(InternalError::BadPartyCount)
+        );
+        assert_eq!(
+            Param::builder()
+                .cnc_param(MAX_CNC_PARAM + 1)
+                .rep_param(1)
+                .build(),
+            Err(InternalError::BadCncParam)
+        );
+        assert_eq!(
+            Param::builder().rep_param(0).build(),
+            Err(InternalError::BadRepParam)
+        );
+        // the largest parameter fits the proof encoding
+        let param = Param::new(
+            MAX_SSP_DIMENSION,
+            MAX_PARTY_COUNT,
+            MAX_CNC_PARAM,
+            63,
+            MAX_CNC_PARAM,
+        );
+        assert!(NIProverMsg::<L1>::max_size(param.unwrap()).is_some());
+        // tau must not be larger than M
+        assert_eq!(
+            Param::builder().cnc_param(10).rep_param(11).build(),
+            Err(InternalError::BadRepParam)
+        );
+        assert_eq!(
+            Param::builder().abort_param(64).build(),
+            Err(InternalError::BadAbortParam)
+        );
+
+        // deserialization also validates
+        let json = serde_json::to_string(&Param::L1_SHORT).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Param>(&json).unwrap(),
+            Param::L1_SHORT
+        );
+        let bad_json = json.replace("\"rep_param\":33", "\"rep_param\":4097");
+        assert!(serde_json::from_str::<Param>(&bad_json).is_err());
+    }
+
+    #[test]
+    fn test_analyze() {
+        // without unchallenged instances all instances can be bad
+        let param = Param::new(128, 4, 10, 14, 10).unwrap();
+        assert_eq!(param.soundness_bits(), 0.0);
+
+        let analysis = Param::default().analyze::<L1>();
+        // 48 bits for one run, and the prover can restart 15 times
+        assert_eq!(Param::default().max_restarts(), 15);
+        assert!((analysis.soundness_bits - (48.0 - 16f64.log2())).abs() < 1e-9);
+        // grinding J and L separately is much cheaper than 2^48
+        assert!((analysis.fs_soundness_bits - 26.884).abs() < 1e-3);
+        assert_eq!(analysis.proof_size, 27700);
+        // 1 - (1 - 2^-14)^128, only the share of the unopened party counts
+        assert!((analysis.abort_probability - 0.007782).abs() < 1e-5);
+
+        // the parameters that only had 128 bits of interactive soundness
+        let old_fast = Param::new(128, 4, 256, 14, 64).unwrap();
+        assert!((old_fast.fs_soundness_bits() - 69.755).abs() < 1e-3);
+        let old_short = Param::new(128, 256, 4096, 20, 16).unwrap();
+        assert!((old_short.fs_soundness_bits() - 67.205).abs() < 1e-3);
+
+        let analysis = Param::L1_FAST.analyze::<L1>();
+        assert!(analysis.fs_soundness_bits >= 128.0);
+        assert!(analysis.soundness_bits >= analysis.fs_soundness_bits);
+        assert_eq!(analysis.proof_size, 103362);
+
+        let analysis = Param::L1_SHORT.analyze::<L1>();
+        assert!(analysis.fs_soundness_bits >= 128.0);
+        assert!(analysis.soundness_bits >= analysis.fs_soundness_bits);
+        assert_eq!(analysis.proof_size, 43653);
+
+        let analysis = Param::L5_FAST.analyze::<L5>();
+        assert!(analysis.fs_soundness_bits >= 256.0);
+        assert!(analysis.soundness_bits >= analysis.fs_soundness_bits);
+        assert_eq!(analysis.proof_size, 240034);
+
+        let analysis = Param::L5_SHORT.analyze::<L5>();
+        assert!(analysis.fs_soundness_bits >= 256.0);
+        assert!(analysis.soundness_bits >= analysis.fs_soundness_bits);
+        assert_eq!(analysis.proof_size, 112036);
+    }
+
+    #[test]
+    fn test_max_restarts() {
+        // an honest prover fails with probability at most 2^-40
+        for param in [Param::default(), Param::L1_SHORT, Param::L5_SHORT] {
+            let k = param.max_restarts();
+            let p = param.restart_probability();
+            assert!(p.powi(k as i32 + 1) <= 2f64.powi(-40));
+            assert!(p.powi(k as i32) > 2f64.powi(-40));
+        }
+
+        // instances that never abort need no restart
+        let param = Param::new(1, 2, 10, 63, 1).unwrap();
+        assert_eq!(param.max_restarts(), 0);
+        // instances that always abort are capped
+        let param = Param::new(128, 4, 10, 0, 1).unwrap();
+        assert_eq!(param.restart_probability(), 1.0);
+        assert_eq!(param.max_restarts(), MAX_RESTARTS);
+    }
+
+    #[test]
+    fn test_search() {
+        assert_eq!(Param::search::<L1>(128, 24, 128.0), Some(Param::L1_SHORT));
+
+        let param = Param::search::<L1>(64, 14, 80.0).unwrap();
+        assert!(param.fs_soundness_bits() >= 80.0);
+
+        // the smallest proof cannot be larger than the presets
+        let param = Param::search::<L1>(128, 20, 128.0).unwrap();
+        assert!(NIProverMsg::<L1>::max_size(param) <= NIProverMsg::<L1>::max_size(Param::L1_FAST));
+
+        assert_eq!(Param::search::<L5>(128, 24, 256.0), Some(Param::L5_SHORT));
+
+        assert_eq!(Param::search::<L1>(128, 14, 10000.0), None);
+    }
+}
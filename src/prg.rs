@@ -0,0 +1,233 @@
+//! The PRGs used to expand the seeds.
+//! Like the hash function, the PRG is part of the security level,
+//! e.g., `L1<Sha3_256, Shake>` only uses SHA3 primitives.
+//! The key size of the security level selects the instance of the PRG,
+//! e.g., AES-128 for 16 byte seeds and AES-256 for 32 byte seeds.
+
+use crate::consts::PREFIX_PRG;
+use aes::cipher::{Block, BlockEncrypt, Key, KeyInit};
+use generic_array::{
+    typenum::{U16, U32},
+    ArrayLength, GenericArray,
+};
+use sha3::digest::{ExtendableOutput, Update};
+
+/// A PRG with seeds of `K` bytes and IVs of `B` bytes.
+pub trait Prg<K: ArrayLength<u8>, B: ArrayLength<u8>>: Send + Sync + 'static {
+    /// The identifier of the PRG.
+    const ID: u8;
+
+    /// Fill `out` with the output of the PRG,
+    /// a shorter output is always a prefix of a longer one.
+    fn fill_bytes(seed: &GenericArray<u8, K>, iv: &GenericArray<u8, B>, out: &mut [u8]);
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// AES in counter mode.
+/// The IV is the first counter block where the last 8 bytes
+/// are a big endian counter, beware that the counter type is u64.
+pub struct AesCtr;
+
+impl Prg<U16, U16> for AesCtr {
+    const ID: u8 = 1;
+
+    fn fill_bytes(seed: &GenericArray<u8, U16>, iv: &GenericArray<u8, U16>, out: &mut [u8]) {
+        aes_ctr_fill::<aes::Aes128>(seed, iv, out);
+    }
+}
+
+impl Prg<U32, U16> for AesCtr {
+    const ID: u8 = 1;
+
+    fn fill_bytes(seed: &GenericArray<u8, U32>, iv: &GenericArray<u8, U16>, out: &mut [u8]) {
+        aes_ctr_fill::<aes::Aes256>(seed, iv, out);
+    }
+}
+
+/// Encrypt `block_count` counter blocks starting from `iv`.
+pub(crate) fn aes_ctr_blocks<C: BlockEncrypt + KeyInit>(
+    seed: &Key<C>,
+    iv: &Block<C>,
+    block_count: usize,
+) -> Vec<Block<C>> {
+    let cipher = C::new(seed);
+    let ctr_start = iv.len() - 8;
+    let ctr = u64::from_be_bytes(iv[ctr_start..].try_into().expect("must be 8 bytes"));
+    let mut blocks: Vec<_> = (0..block_count as u64)
+        .map(|i| {
+            let mut block = iv.clone();
+            block[ctr_start..].copy_from_slice(&ctr.wrapping_add(i).to_be_bytes());
+            block
+        })
+        .collect();
+    cipher.encrypt_blocks(&mut blocks);
+    blocks
+}
+
+fn aes_ctr_fill<C: BlockEncrypt + KeyInit>(seed: &Key<C>, iv: &Block<C>, out: &mut [u8]) {
+    let block_size = C::block_size();
+    let blocks = aes_ctr_blocks::<C>(seed, iv, out.len().div_ceil(block_size));
+    for (chunk, block) in out.chunks_mut(block_size).zip(&blocks) {
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// SHAKE128 for 16 byte seeds and SHAKE256 for 32 byte seeds,
+/// the output is the output stream of the XOF on the seed and the IV.
+pub struct Shake;
+
+impl<B: ArrayLength<u8>> Prg<U16, B> for Shake {
+    const ID: u8 = 2;
+
+    fn fill_bytes(seed: &GenericArray<u8, U16>, iv: &GenericArray<u8, B>, out: &mut [u8]) {
+        xof_fill::<sha3::Shake128>(seed, iv, out);
+    }
+}
+
+impl<B: ArrayLength<u8>> Prg<U32, B> for Shake {
+    const ID: u8 = 2;
+
+    fn fill_bytes(seed: &GenericArray<u8, U32>, iv: &GenericArray<u8, B>, out: &mut [u8]) {
+        xof_fill::<sha3::Shake256>(seed, iv, out);
+    }
+}
+
+fn xof_fill<X: Default + Update + ExtendableOutput>(seed: &[u8], iv: &[u8], out: &mut [u8]) {
+    let mut xof = X::default();
+    xof.update(&PREFIX_PRG);
+    xof.update(seed);
+    xof.update(iv);
+    xof.finalize_xof_into(out);
+}
+
+#[cfg(feature = "chacha20")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// XChaCha20, it is fast on machines without AES instructions.
+/// The key is the seed zero padded to 32 bytes
+/// and the nonce is the IV zero padded or truncated to 24 bytes.
+pub struct ChaCha20;
+
+#[cfg(feature = "chacha20")]
+impl<B: ArrayLength<u8>> Prg<U16, B> for ChaCha20 {
+    const ID: u8 = 3;
+
+    fn fill_bytes(seed: &GenericArray<u8, U16>, iv: &GenericArray<u8, B>, out: &mut [u8]) {
+        xchacha_fill(seed, iv, out);
+    }
+}
+
+#[cfg(feature = "chacha20")]
+impl<B: ArrayLength<u8>> Prg<U32, B> for ChaCha20 {
+    const ID: u8 = 3;
+
+    fn fill_bytes(seed: &GenericArray<u8, U32>, iv: &GenericArray<u8, B>, out: &mut [u8]) {
+        xchacha_fill(seed, iv, out);
+    }
+}
+
+#[cfg(feature = "chacha20")]
+fn xchacha_fill(seed: &[u8], iv: &[u8], out: &mut [u8]) {
+    use chacha20::cipher::{KeyIvInit, StreamCipher};
+
+    let mut key = chacha20::Key::default();
+    key[..seed.len()].copy_from_slice(seed);
+    let mut nonce = chacha20::XNonce::default();
+    let len = iv.len().min(nonce.len());
+    nonce[..len].copy_from_slice(&iv[..len]);
+
+    out.fill(0);
+    chacha20::XChaCha20::new(&key, &nonce).apply_keystream(out);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aes::cipher::{KeyIvInit, StreamCipher};
+
+    fn output<K, B, P>(seed: &GenericArray<u8, K>, iv: &GenericArray<u8, B>, n: usize) -> Vec<u8>
+    where
+        K: ArrayLength<u8>,
+        B: ArrayLength<u8>,
+        P: Prg<K, B>,
+    {
+        let mut out = vec![0u8; n];
+        P::fill_bytes(seed, iv, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_prg() {
+        let seed = Key::<aes::Aes128>::default();
+        let iv = Block::<aes::Aes128>::default();
+        let out1 = aes_ctr_blocks::<aes::Aes128>(&seed, &iv, 1);
+        assert_eq!(out1.len(), 1);
+        assert_eq!(out1[0].len(), 16);
+
+        let out2 = aes_ctr_blocks::<aes::Aes128>(&seed, &iv, 2);
+        assert_eq!(out2.len(), 2);
+
+        let seed2 = Key::<aes::Aes128>::from([1u8; 16]);
+        let out3 = aes_ctr_blocks::<aes::Aes128>(&seed2, &iv, 1);
+        assert_ne!(out1, out3);
+
+        // AES-256 is used for 32 byte seeds
+        let out4 = aes_ctr_blocks::<aes::Aes256>(&Default::default(), &iv, 1);
+        assert_ne!(out1, out4);
+    }
+
+    #[test]
+    fn test_prg_ctr64() {
+        // the keystream must be the same as the one of the ctr crate
+        let seed = Key::<aes::Aes128>::from([3u8; 16]);
+        let mut iv = Block::<aes::Aes128>::from([5u8; 16]);
+        // make the counter wrap around
+        iv[8..].copy_from_slice(&(u64::MAX - 1).to_be_bytes());
+
+        let mut expected = [0u8; 64];
+        ctr::Ctr64BE::<aes::Aes128>::new(&seed, &iv).apply_keystream(&mut expected);
+        let actual: Vec<u8> = aes_ctr_blocks::<aes::Aes128>(&seed, &iv, 4).concat();
+        assert_eq!(actual, expected);
+        assert_eq!(output::<_, _, AesCtr>(&seed, &iv, 20), expected[..20]);
+    }
+
+    fn check_backend<P: Prg<U16, U16> + Prg<U32, U16>>() {
+        let seed = GenericArray::<u8, U16>::from([3u8; 16]);
+        let iv = GenericArray::<u8, U16>::from([5u8; 16]);
+
+        // a shorter output is a prefix of a longer one
+        let long = output::<_, _, P>(&seed, &iv, 100);
+        assert_eq!(output::<_, _, P>(&seed, &iv, 33), long[..33]);
+
+        // the output depends on the seed and the IV
+        let seed2 = GenericArray::<u8, U16>::from([4u8; 16]);
+        let iv2 = GenericArray::<u8, U16>::from([6u8; 16]);
+        assert_ne!(output::<_, _, P>(&seed2, &iv, 100), long);
+        assert_ne!(output::<_, _, P>(&seed, &iv2, 100), long);
+
+        // 32 byte seeds are supported
+        let seed3 = GenericArray::<u8, U32>::from([3u8; 32]);
+        assert_ne!(output::<_, _, P>(&seed3, &iv, 100), long);
+    }
+
+    #[test]
+    fn test_backends() {
+        check_backend::<AesCtr>();
+        check_backend::<Shake>();
+        #[cfg(feature = "chacha20")]
+        check_backend::<ChaCha20>();
+
+        // different backends give different outputs
+        let seed = GenericArray::<u8, U16>::default();
+        let iv = GenericArray::<u8, U16>::default();
+        assert_ne!(
+            output::<_, _, AesCtr>(&seed, &iv, 32),
+            output::<_, _, Shake>(&seed, &iv, 32)
+        );
+        #[cfg(feature = "chacha20")]
+        assert_ne!(
+            output::<_, _, ChaCha20>(&seed, &iv, 32),
+            output::<_, _, Shake>(&seed, &iv, 32)
+        );
+    }
+}
@@ -1,324 +1,284 @@
 use crate::consts::*;
-use aes::cipher::{Block, IvSizeUser, KeyIvInit, KeySizeUser, StreamCipherCore};
+use crate::hash::ProtocolHash;
+use crate::prg::Prg;
+use crate::security::{hex_array, Block, DigestOutput, Key, OpeningArray, SecurityParams};
+use generic_array::{ArrayLength, GenericArray};
 use serde::{Serialize, Serializer};
-use sha3::{Digest, Sha3_256};
-use std::collections::VecDeque;
-
-type Aes128Ctr = ctr::CtrCore<aes::Aes128, ctr::flavors::Ctr64BE>;
-type PrgBlock = Block<aes::Aes128>;
 
 #[derive(Debug, Eq, PartialEq)]
 /// A hash-based opening of a commitment, created by the prover.
-pub struct Opening {
-    pub(crate) inner: [u8; OPENING_SIZE],
+pub struct Opening<S: SecurityParams> {
+    pub(crate) inner: OpeningArray<S>,
 }
 
-impl Serialize for Opening {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+impl<S: SecurityParams> Serialize for Opening<S> {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
     where
-        S: Serializer,
+        Ser: Serializer,
     {
-        hex::serde::serialize(self.inner, serializer)
+        hex_array::serialize(&self.inner, serializer)
     }
 }
 
-impl Opening {
-    pub(crate) fn new(c: [u8; OPENING_SIZE]) -> Self {
+impl<S: SecurityParams> Opening<S> {
+    pub(crate) fn new(c: OpeningArray<S>) -> Self {
         Self { inner: c }
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 /// A hash-based commitment, created by the prover.
-pub struct Commitment {
-    // Usually we'd use Commitment(pub(crate) [u8; DIGEST_SIZE]),
+pub struct Commitment<S: SecurityParams> {
+    // Usually we'd use Commitment(pub(crate) DigestOutput<S>),
     // but it seems tricky to make serde use hex encoding on the .0 field
-    pub(crate) inner: [u8; DIGEST_SIZE],
+    pub(crate) inner: DigestOutput<S>,
 }
 
-impl Serialize for Commitment {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+impl<S: SecurityParams> Serialize for Commitment<S> {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
     where
-        S: Serializer,
+        Ser: Serializer,
     {
-        hex::serde::serialize(self.inner, serializer)
+        hex_array::serialize(&self.inner, serializer)
     }
 }
 
-impl Commitment {
-    pub(crate) fn new(c: [u8; DIGEST_SIZE]) -> Self {
+impl<S: SecurityParams> Commitment<S> {
+    pub(crate) fn new(c: DigestOutput<S>) -> Self {
         Self { inner: c }
     }
 }
 
-pub(crate) fn fs_hash1(h: &[u8; DIGEST_SIZE]) -> [u8; DIGEST_SIZE] {
-    let mut hasher = Sha3_256::new();
+/// The Fiat-Shamir hash for the first challenge,
+/// `ctx` is bound into the challenge, e.g., the message of a signature.
+pub(crate) fn fs_hash1<S: SecurityParams>(ctx: &[u8], h: &DigestOutput<S>) -> DigestOutput<S> {
+    let mut hasher = S::Hash::new();
     hasher.update(PREFIX_FS_H1);
+    hasher.update(ctx.len().to_le_bytes());
+    hasher.update(ctx);
     hasher.update(h);
-    let result = hasher.finalize();
-    result.as_slice().try_into().unwrap()
+    hasher.finalize()
 }
 
-pub(crate) fn fs_hash2(
-    h_prime: &[u8; DIGEST_SIZE],
-    mseeds: &[[u8; BLOCK_SIZE]],
-) -> [u8; DIGEST_SIZE] {
-    let mut hasher = Sha3_256::new();
+/// The Fiat-Shamir hash for the second challenge,
+/// `ctx` is bound into the challenge, e.g., the message of a signature.
+pub(crate) fn fs_hash2<S: SecurityParams>(
+    ctx: &[u8],
+    h_prime: &DigestOutput<S>,
+    mseeds: &[Key<S>],
+) -> DigestOutput<S> {
+    let mut hasher = S::Hash::new();
     hasher.update(PREFIX_FS_H2);
+    hasher.update(ctx.len().to_le_bytes());
+    hasher.update(ctx);
     hasher.update(h_prime);
-    hasher.update(&mseeds.len().to_le_bytes());
+    hasher.update(mseeds.len().to_le_bytes());
     for mseed in mseeds {
         hasher.update(mseed);
     }
-    let result = hasher.finalize();
-    result.as_slice().try_into().unwrap()
+    hasher.finalize()
 }
 
-pub(crate) fn hash1(delta_rs: &[u64], coms: &[Commitment]) -> [u8; DIGEST_SIZE] {
-    let mut hasher = Sha3_256::new();
+pub(crate) fn hash1<S: SecurityParams>(
+    delta_rs: &[u64],
+    coms: &[Commitment<S>],
+) -> DigestOutput<S> {
+    let mut hasher = S::Hash::new();
     hasher.update(PREFIX_H1_DELTA);
-    hasher.update(&delta_rs.len().to_le_bytes());
+    hasher.update(delta_rs.len().to_le_bytes());
     for delta_r in delta_rs {
-        hasher.update(&delta_r.to_le_bytes());
+        hasher.update(delta_r.to_le_bytes());
     }
     hasher.update(PREFIX_H1_COM);
-    hasher.update(&coms.len().to_le_bytes());
+    hasher.update(coms.len().to_le_bytes());
     for com in coms {
         hasher.update(&com.inner);
     }
-
-    let result = hasher.finalize();
-    result.as_slice().try_into().unwrap()
+    hasher.finalize()
 }
 
-pub(crate) fn hash2(h1s: &[[u8; DIGEST_SIZE]]) -> [u8; DIGEST_SIZE] {
-    let mut hasher = Sha3_256::new();
+pub(crate) fn hash2<S: SecurityParams>(h1s: &[DigestOutput<S>]) -> DigestOutput<S> {
+    let mut hasher = S::Hash::new();
     hasher.update(PREFIX_H2);
-    hasher.update(&h1s.len().to_le_bytes());
+    hasher.update(h1s.len().to_le_bytes());
     for h1 in h1s {
         hasher.update(h1);
     }
-
-    let result = hasher.finalize();
-    result.as_slice().try_into().unwrap()
+    hasher.finalize()
 }
 
-pub(crate) fn hash3<J>(rs_tilde: &[u8], t_shares: J) -> [u8; DIGEST_SIZE]
+pub(crate) fn hash3<S: SecurityParams, J>(rs_tilde: &[u8], t_shares: J) -> DigestOutput<S>
 where
-    J: Iterator<Item = u64>,
+    J: ExactSizeIterator<Item = u64>,
 {
-    let mut hasher = Sha3_256::new();
+    let mut hasher = S::Hash::new();
     hasher.update(PREFIX_H3);
-    hasher.update(&rs_tilde.len().to_le_bytes());
+    hasher.update(rs_tilde.len().to_le_bytes());
     hasher.update(rs_tilde);
-
-    // TODO add a prefix for number of t_shares for domain separation
+    hasher.update(t_shares.len().to_le_bytes());
     for t_share in t_shares {
-        hasher.update(&t_share.to_le_bytes());
+        hasher.update(t_share.to_le_bytes());
     }
-
-    let result = hasher.finalize();
-    result.as_slice().try_into().unwrap()
+    hasher.finalize()
 }
 
-pub(crate) fn hash4<I>(h_primes: I) -> [u8; DIGEST_SIZE]
+pub(crate) fn hash4<S: SecurityParams, I>(h_primes: I) -> DigestOutput<S>
 where
-    I: Iterator<Item = [u8; DIGEST_SIZE]>,
+    I: ExactSizeIterator<Item = DigestOutput<S>>,
 {
-    let mut hasher = Sha3_256::new();
+    let mut hasher = S::Hash::new();
     hasher.update(PREFIX_H4);
-
-    // TODO add a prefix for number of h_prime for domain separation
+    hasher.update(h_primes.len().to_le_bytes());
     for h_prime in h_primes {
-        hasher.update(&h_prime);
+        hasher.update(h_prime);
     }
-
-    let result = hasher.finalize();
-    result.as_slice().try_into().unwrap()
+    hasher.finalize()
 }
 
-pub(crate) fn commit(value: &[u8], opening: &Opening) -> Commitment {
-    debug_assert_eq!(Sha3_256::output_size(), DIGEST_SIZE);
-    let mut hasher = Sha3_256::new();
-    hasher.update(opening.inner);
+pub(crate) fn commit<S: SecurityParams>(value: &[u8], opening: &Opening<S>) -> Commitment<S> {
+    let mut hasher = S::Hash::new();
+    hasher.update(&opening.inner);
     hasher.update(value);
-    let result = hasher.finalize();
-    // TODO avoid copying, change to generic array?
-    Commitment::new(result.as_slice().try_into().unwrap())
+    Commitment::new(hasher.finalize())
 }
 
-pub(crate) fn verify(value: &[u8], opening: &Opening, commitment: &Commitment) -> bool {
-    let actual = commit(value, opening);
-    actual == *commitment
+/// Create an IV from a constant label,
+/// the label is zero padded or truncated to the block size.
+pub(crate) fn iv_from_label<S: SecurityParams>(label: &[u8]) -> Block<S> {
+    let mut iv = Block::<S>::default();
+    let len = label.len().min(iv.len());
+    iv[..len].copy_from_slice(&label[..len]);
+    iv
 }
 
-/// An AES counter mode based PRG.
-/// Beware that the counter type is u64.
-pub(crate) fn prg_aes_ctr(
-    seed: &[u8; KEY_SIZE],
-    iv: &[u8; BLOCK_SIZE],
-    block_count: usize,
-) -> Vec<[u8; BLOCK_SIZE]> {
-    let mut cipher = Aes128Ctr::new(seed.into(), iv.into());
-    debug_assert_eq!(Aes128Ctr::key_size(), KEY_SIZE);
-    debug_assert_eq!(Aes128Ctr::iv_size(), BLOCK_SIZE);
-    let mut blocks = vec![PrgBlock::default(); block_count];
-    cipher.apply_keystream_blocks(&mut blocks);
-    blocks
-        .into_iter()
-        .map(|gblock| gblock.as_slice().try_into().unwrap())
-        .collect()
+/// Generate `n` bytes using the PRG of the security level.
+fn prg_bytes<S: SecurityParams>(seed: &Key<S>, iv: &Block<S>, n: usize) -> Vec<u8> {
+    let mut out = vec![0u8; n];
+    S::Prg::fill_bytes(seed, iv, &mut out);
+    out
 }
 
-/// An AES counter mode based PRG that generates a vector of u64.
-pub(crate) fn prg_u64(seed: &[u8; KEY_SIZE], iv: &[u8; BLOCK_SIZE], n: usize) -> Vec<u64> {
+/// A PRG that generates a vector of u64.
+pub(crate) fn prg_u64<S: SecurityParams>(seed: &Key<S>, iv: &Block<S>, n: usize) -> Vec<u64> {
     const U64_BYTES: usize = u64::BITS as usize / 8;
-    assert_eq!(BLOCK_SIZE % U64_BYTES, 0);
-    let u64_per_block = BLOCK_SIZE / U64_BYTES;
-    let block_count = (n + u64_per_block - 1) / u64_per_block;
-    let blocks = prg_aes_ctr(seed, iv, block_count);
-
-    let mut out = vec![0u64; n];
-    for (i, block) in blocks.into_iter().enumerate() {
-        let per_block = if i < block_count - 1 {
-            u64_per_block
-        } else {
-            u64_per_block - (u64_per_block * block_count - n)
-        };
-        for j in 0..per_block {
-            out[i * u64_per_block + j] = u64::from_le_bytes(
-                block[j * U64_BYTES..j * U64_BYTES + 8]
-                    .try_into()
-                    .expect("must be 8 bytes"),
-            );
-        }
-    }
-    out
+    prg_bytes::<S>(seed, iv, n * U64_BYTES)
+        .chunks_exact(U64_BYTES)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("must be 8 bytes")))
+        .collect()
 }
 
-/// An AES counter mode based PRG that generates bits
+/// A PRG that generates bits
 /// every bit is represented by a u8.
-pub(crate) fn prg_bin(seed: &[u8; KEY_SIZE], iv: &[u8; BLOCK_SIZE], n: usize) -> Vec<u8> {
+pub(crate) fn prg_bin<S: SecurityParams>(seed: &Key<S>, iv: &Block<S>, n: usize) -> Vec<u8> {
     assert!(n >= 1);
-    let block_count = n / BLOCK_SIZE + 1;
-    let blocks = prg_aes_ctr(seed, iv, block_count);
-    let mut out = vec![0u8; n];
-    let mut i = 0usize;
-    for block in blocks {
-        for b in block {
-            for shift in 0u8..8 {
-                out[i] = (b >> shift) & 1;
-                i += 1;
-                if i == n {
-                    return out;
-                }
-            }
-        }
-    }
-    unreachable!()
+    prg_bytes::<S>(seed, iv, n.div_ceil(8))
+        .into_iter()
+        .flat_map(|b| (0u8..8).map(move |shift| (b >> shift) & 1))
+        .take(n)
+        .collect()
 }
 
-/// A length doubling PRG based on AES counter mode.
-pub(crate) fn prg_double(
-    seed: &[u8; KEY_SIZE],
-    iv: &[u8; BLOCK_SIZE],
-) -> ([u8; BLOCK_SIZE], [u8; BLOCK_SIZE]) {
-    let out = prg_aes_ctr(seed, iv, 2);
-    (out[0], out[1])
+/// A PRG that generates two arrays,
+/// the sizes of the arrays are given by their types.
+pub(crate) fn prg_pair<S, A, B>(
+    seed: &Key<S>,
+    iv: &Block<S>,
+) -> (GenericArray<u8, A>, GenericArray<u8, B>)
+where
+    S: SecurityParams,
+    A: ArrayLength<u8>,
+    B: ArrayLength<u8>,
+{
+    let out = prg_bytes::<S>(seed, iv, A::USIZE + B::USIZE);
+    let (left, right) = out.split_at(A::USIZE);
+    (
+        GenericArray::clone_from_slice(left),
+        GenericArray::clone_from_slice(right),
+    )
 }
 
-/// A GGM tree PRG based on AES counter mode.
-/// Internally, it is implemented using a queue since it is
-/// easier to build an unbalanced tree when compared to the recursive method.
-pub(crate) fn prg_tree(
-    seed: &[u8; KEY_SIZE],
-    iv: &[u8; BLOCK_SIZE],
-    n: usize,
-) -> Vec<[u8; BLOCK_SIZE]> {
-    let mut out = VecDeque::with_capacity(n);
-    while out.len() < n {
-        if out.is_empty() {
-            // NOTE: this assumes the key size is the same as the block size
-            out.push_back(*seed);
-            continue;
-        }
-        let new_seed: [u8; KEY_SIZE] = out.pop_front().expect("deque should be initialized here");
-        let (left, right) = prg_double(&new_seed, iv);
-        out.push_back(left);
-        out.push_back(right);
-    }
-    out.into()
+/// A length doubling PRG.
+pub(crate) fn prg_double<S: SecurityParams>(seed: &Key<S>, iv: &Block<S>) -> (Key<S>, Key<S>) {
+    prg_pair::<S, S::KeySize, S::KeySize>(seed, iv)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::security::{digest_size, key_size, L1, L5};
+    use generic_array::typenum::Unsigned;
 
-    #[test]
-    fn test_commit() {
+    fn check_commit<S: SecurityParams>() {
         let value = [0u8, 1, 2, 3];
-        let opening = Opening::new([1u8; OPENING_SIZE]);
+        let opening = Opening::<S>::new(OpeningArray::<S>::clone_from_slice(&vec![
+            1u8;
+            S::OpeningSize::USIZE
+        ]));
         let commitment = commit(&value, &opening);
-        assert!(verify(&value, &opening, &commitment));
+        assert_eq!(commitment.inner.len(), digest_size::<S>());
+        assert_eq!(commit(&value, &opening), commitment);
 
-        let bad_opening = Opening::new([2u8; OPENING_SIZE]);
-        assert!(!verify(&value, &bad_opening, &commitment));
+        let bad_opening = Opening::<S>::new(OpeningArray::<S>::clone_from_slice(&vec![
+            2u8;
+            S::OpeningSize::USIZE
+        ]));
+        assert_ne!(commit(&value, &bad_opening), commitment);
 
         let bad_value = [0u8, 1, 2, 2];
-        assert!(!verify(&bad_value, &opening, &commitment));
+        assert_ne!(commit(&bad_value, &opening), commitment);
     }
 
     #[test]
-    fn test_prg() {
-        let seed = [0u8; KEY_SIZE];
-        let iv = [0u8; BLOCK_SIZE];
-        let out1 = prg_aes_ctr(&seed, &iv, 1);
-        assert_eq!(out1.len(), 1);
-        assert_eq!(out1[0].len(), BLOCK_SIZE);
-
-        let out2 = prg_aes_ctr(&seed, &iv, 2);
-        assert_eq!(out2.len(), 2);
-
-        let seed2 = [1u8; KEY_SIZE];
-        let out3 = prg_aes_ctr(&seed2, &iv, 1);
-        assert_ne!(out1, out3);
+    fn test_commit() {
+        check_commit::<L1>();
+        check_commit::<L5>();
     }
 
     #[test]
     fn test_prg_u64() {
-        let seed = [0u8; KEY_SIZE];
-        let iv = [0u8; BLOCK_SIZE];
-        let out1 = prg_u64(&seed, &iv, 1);
+        let seed = Key::<L1>::default();
+        let iv = Block::<L1>::default();
+        let out1 = prg_u64::<L1>(&seed, &iv, 1);
         assert_eq!(out1.len(), 1);
 
-        let out2 = prg_u64(&seed, &iv, 8);
+        let out2 = prg_u64::<L1>(&seed, &iv, 8);
         assert_eq!(out2.len(), 8);
+        assert_eq!(out1[0], out2[0]);
     }
 
     #[test]
     fn test_prg_bin() {
-        let seed = [0u8; KEY_SIZE];
-        let iv = [0u8; BLOCK_SIZE];
+        let seed = Key::<L1>::default();
+        let iv = Block::<L1>::default();
 
-        let out1 = prg_bin(&seed, &iv, 1);
+        let out1 = prg_bin::<L1>(&seed, &iv, 1);
         assert_eq!(out1.len(), 1);
         assert!(out1[0] == 0 || out1[0] == 1);
 
-        let out2 = prg_bin(&seed, &iv, BLOCK_SIZE * 8);
-        assert_eq!(out2.len(), BLOCK_SIZE * 8);
+        let out2 = prg_bin::<L1>(&seed, &iv, 16 * 8);
+        assert_eq!(out2.len(), 16 * 8);
         for b in out2 {
             assert!(b == 0 || b == 1);
         }
     }
 
+    fn check_prg_pair<S: SecurityParams>() {
+        let seed = Key::<S>::default();
+        let iv = Block::<S>::default();
+        let (left, right) = prg_double::<S>(&seed, &iv);
+        assert_eq!(left.len(), key_size::<S>());
+        assert_ne!(left, right);
+
+        // the pair of a seed and an opening uses the same keystream as prg_double
+        let (left, right) = prg_double::<S>(&seed, &iv);
+        let (seed_out, opening_out) = prg_pair::<S, S::KeySize, S::OpeningSize>(&seed, &iv);
+        assert_eq!(left, seed_out);
+        assert_eq!(right[..], opening_out[..]);
+    }
+
     #[test]
-    fn test_prg_tree() {
-        let seed = [0u8; KEY_SIZE];
-        let iv = [0u8; BLOCK_SIZE];
-        let out = prg_tree(&seed, &iv, 2);
-        assert_eq!(out.len(), 2);
-        assert_eq!(out[0].len(), BLOCK_SIZE);
-        assert_eq!(out[1].len(), BLOCK_SIZE);
-        assert_ne!(out[0], out[1]);
+    fn test_prg_pair() {
+        check_prg_pair::<L1>();
+        check_prg_pair::<L5>();
     }
 }
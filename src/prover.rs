@@ -1,88 +1,285 @@
 use crate::primitives::*;
+use crate::seed_tree::SeedTree;
+use crate::session::ProverSession;
 use crate::*;
 use crossbeam::channel::{Receiver, Sender};
 use rand_core::{CryptoRng, RngCore};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
-#[derive(Serialize, Debug)]
-#[serde(transparent)]
-/// WrapperArray is created so that serde knows how to
-/// (de)serialize a vector of arrays using hex.
-struct WrapperArray {
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "ProverRepr<S>", bound = "")]
+/// The prover of the subset sum MPCitH protocol.
+/// It can be (de)serialized together with a `ProverState`
+/// so that a session survives a restart of the process,
+/// the witness is checked again when it is deserialized.
+pub struct Prover<S: SecurityParams = L1> {
+    witness: Witness,
+    instance: Instance<S>,
+    #[serde(with = "hex_array")]
+    mseed: Key<S>,
     #[serde(with = "hex::serde")]
-    inner: [u8; BLOCK_SIZE],
+    iv: Block<S>,
+    param: Param,
+    mode: ProverMode,
 }
 
-impl WrapperArray {
-    fn new(a: [u8; BLOCK_SIZE]) -> Self {
-        Self { inner: a }
-    }
+/// How much of the C&C instances the prover keeps between the steps.
+/// `Full` is the default, `Lean` trades the expansion of J twice for memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProverMode {
+    /// Keep only the master seed and h1 of every instance,
+    /// the tau instances in J are expanded again in step2 and step3.
+    Lean,
+    /// Also keep every expanded instance after step1, so nothing is expanded again,
+    /// but the state takes more than M * N * n * 8 bytes.
+    #[default]
+    Full,
 }
 
-#[derive(Debug, Serialize)]
-/// The prover of the subset sum MPCitH protocol.
-pub struct Prover {
+#[derive(Deserialize)]
+#[serde(bound = "")]
+/// The serialized form of `Prover`, the IV is recomputed from the instance.
+struct ProverRepr<S: SecurityParams> {
     witness: Witness,
-    instance: Instance,
-    #[serde(with = "hex::serde")]
-    mseed: [u8; BLOCK_SIZE],
-    #[serde(with = "hex::serde")]
-    iv: [u8; BLOCK_SIZE],
+    instance: Instance<S>,
+    #[serde(with = "hex_array")]
+    mseed: Key<S>,
     param: Param,
+    #[serde(default)]
+    mode: ProverMode,
 }
 
-/// For each C&C parameter
-#[derive(Debug, Serialize)]
-pub struct ProverStateInner {
-    #[serde(with = "hex::serde")]
-    mseed_inner: [u8; BLOCK_SIZE],
-    #[serde(with = "hex::serde")]
-    rs: Vec<u8>,
-    // usually it should be Vec<[u8; BLOCK_SIZE]>,
-    seeds: Vec<WrapperArray>,
-    rhos: Vec<Opening>,
-    r_shares: Vec<Vec<u64>>,
-    coms: Vec<Commitment>,
-    r_shares_sum: Vec<u64>,
-    delta_rs: Vec<u64>,
-    #[serde(with = "hex::serde")]
-    h1: [u8; DIGEST_SIZE],
+impl<S: SecurityParams> TryFrom<ProverRepr<S>> for Prover<S> {
+    type Error = InternalError;
+
+    fn try_from(repr: ProverRepr<S>) -> Result<Self, Self::Error> {
+        Prover::from_witness_instance(repr.witness, repr.instance, repr.mseed, repr.param)
+            .map(|prover| prover.with_mode(repr.mode))
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ProverState {
-    step1_state: Vec<ProverStateInner>,
-    #[serde(with = "hex::serde")]
-    h: [u8; DIGEST_SIZE],
+/// For each C&C parameter, only the master seed and h1 are kept
+/// since the instance is recomputed from the master seed on demand.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ProverStateInner<S: SecurityParams = L1> {
+    #[serde(with = "hex_array")]
+    mseed_inner: Key<S>,
+    #[serde(with = "hex_array")]
+    h1: DigestOutput<S>,
+}
+
+/// The state of the prover between the steps of the protocol.
+/// It is small and can be (de)serialized,
+/// so that a session can be resumed with `Prover::resume`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ProverState<S: SecurityParams = L1> {
+    step1_state: Vec<ProverStateInner<S>>,
+    #[serde(with = "hex_array")]
+    h: DigestOutput<S>,
+    /// The first challenge J, recorded before it is answered
+    chal1: Vec<usize>,
+    /// The second challenge L, recorded before it is answered
+    chal2: Vec<usize>,
+    /// The number of instances in J that abort given L
+    abort_count: usize,
+    /// The expanded instances, only kept in `ProverMode::Full`
+    #[serde(skip)]
+    instances: Vec<CncInstance<S>>,
+}
+
+/// The step at which a saved session resumes, see `Prover::resume`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeStep {
+    /// J is not recorded yet, the next step is `Prover::step2`.
+    Step2,
+    /// J is recorded, `Prover::step2` only answers that J again
+    /// and the next step is `Prover::step3`,
+    /// which only answers L again if L is also recorded.
+    Step3,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+/// Abort statistics of the prover over one or more runs of the protocol.
+pub struct AbortStats {
+    /// The number of C&C instances in J that are checked for an abort in step3.
+    pub instance_count: usize,
+    /// The number of those instances that abort.
+    pub abort_count: usize,
+    /// The number of restarts because an instance in J aborts.
+    pub restart_count: usize,
 }
 
-impl ProverState {
+impl AbortStats {
+    /// Add the instances in J of one run of step3,
+    /// nothing is added if L is not recorded in `state`.
+    pub fn record<S: SecurityParams>(&mut self, state: &ProverState<S>) {
+        self.instance_count += state.chal2.len();
+        self.abort_count += state.abort_count();
+    }
+
+    /// The fraction of the instances that abort,
+    /// it estimates `Param::abort_probability`.
+    pub fn abort_rate(&self) -> f64 {
+        if self.instance_count == 0 {
+            return 0.0;
+        }
+        self.abort_count as f64 / self.instance_count as f64
+    }
+}
+
+impl<S: SecurityParams> ProverState<S> {
     fn new() -> Self {
         Self {
             step1_state: vec![],
-            h: [0u8; DIGEST_SIZE],
+            h: DigestOutput::<S>::default(),
+            chal1: vec![],
+            chal2: vec![],
+            abort_count: 0,
+            instances: vec![],
         }
     }
 
-    fn set_h(&mut self, h: [u8; DIGEST_SIZE]) {
+    pub fn get_h(&self) -> DigestOutput<S> {
+        self.h.clone()
+    }
+
+    /// The number of C&C instances in J that abort given L,
+    /// the protocol has to be restarted if it is not zero.
+    pub fn abort_count(&self) -> usize {
+        self.abort_count
+    }
+
+    fn set_chal1(&mut self, chal1: Vec<usize>) {
+        self.chal1 = chal1
+    }
+
+    fn set_chal2(&mut self, chal2: Vec<usize>) {
+        self.chal2 = chal2
+    }
+
+    fn set_h(&mut self, h: DigestOutput<S>) {
         self.h = h
     }
 
-    fn push_inner(&mut self, inner: ProverStateInner) {
+    fn push_inner(&mut self, inner: ProverStateInner<S>) {
         self.step1_state.push(inner)
     }
 }
 
-impl Prover {
+/// One C&C instance expanded from its master seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CncInstance<S: SecurityParams> {
+    rs: Vec<u8>,
+    r_shares: Vec<Vec<u64>>,
+    coms: Vec<Commitment<S>>,
+    delta_rs: Vec<u64>,
+    h1: DigestOutput<S>,
+}
+
+impl<S: SecurityParams> CncInstance<S> {
+    /// Expand one C&C instance from its master seed.
+    /// The verifier uses the same function to recompute
+    /// the instances that are not in the challenge J.
+    pub(crate) fn new(mseed_inner: &Key<S>, iv: &Block<S>, param: Param) -> Self {
+        let (tree_root, rs_seed) = prg_double::<S>(mseed_inner, iv);
+        let rs = prg_bin::<S>(&rs_seed, iv, param.ssp_dimension);
+        // every leaf is expanded into the seed and the opening of one party
+        let tree = SeedTree::<S>::expand(&tree_root, iv, param.party_count);
+        let (seeds, rhos): (Vec<_>, Vec<_>) = tree
+            .leaves()
+            .iter()
+            .map(|leaf| {
+                let (seed, rho) = prg_pair::<S, S::KeySize, S::OpeningSize>(leaf, iv);
+                (seed, Opening::new(rho))
+            })
+            .unzip();
+        debug_assert_eq!(seeds.len(), param.party_count);
+        debug_assert_eq!(rhos.len(), param.party_count);
+
+        let r_shares: Vec<Vec<u64>> = seeds
+            .iter()
+            .map(|seed| r_share_from_seed::<S>(seed, iv, param))
+            .collect();
+
+        let coms: Vec<_> = seeds
+            .iter()
+            .zip(rhos.iter())
+            .map(|(seed, rho)| commit(seed, rho))
+            .collect();
+
+        // sum over the N vectors
+        let r_shares_sum: Vec<_> =
+            r_shares
+                .iter()
+                .fold(vec![0u64; param.ssp_dimension], |acc, x| {
+                    acc.into_iter()
+                        .zip(x)
+                        .map(|(a, b)| a.wrapping_add(*b))
+                        .collect()
+                });
+        let delta_rs: Vec<_> = rs
+            .iter()
+            .zip(&r_shares_sum)
+            .map(|(r, share)| (*r as u64).wrapping_sub(*share))
+            .collect();
+
+        let h1 = hash1(&delta_rs, &coms);
+
+        Self {
+            rs,
+            r_shares,
+            coms,
+            delta_rs,
+            h1,
+        }
+    }
+
+    /// Whether the instance cannot be opened with `ell` as the unopened party.
+    /// The opened shares and delta_r reveal r - s, where s is the share of ell,
+    /// which gives away r if s is at the boundary of [0, A),
+    /// i.e., s = 0 when r = 1 or s = A - 1 when r = 0.
+    /// Only the share of ell is checked, so the opened shares are independent of r,
+    /// see `Param::abort_probability`.
+    pub(crate) fn aborts(&self, ell: usize, param: Param) -> bool {
+        let boundary = (1u64 << param.abort_param) - 1;
+        self.r_shares[ell]
+            .iter()
+            .zip(&self.rs)
+            .any(|(share, r)| *share == if *r == 1 { 0 } else { boundary })
+    }
+
+    /// The seed tree of the parties of the instance with master seed `mseed_inner`.
+    /// Its root and the seed of r are the two halves of a length doubling PRG,
+    /// so the nodes that are revealed in step 3 say nothing about r,
+    /// which masks the witness in xs_tilde.
+    pub(crate) fn party_tree(mseed_inner: &Key<S>, iv: &Block<S>, param: Param) -> SeedTree<S> {
+        let (tree_root, _) = prg_double::<S>(mseed_inner, iv);
+        SeedTree::<S>::expand(&tree_root, iv, param.party_count)
+    }
+
+    pub(crate) fn get_h1(&self) -> DigestOutput<S> {
+        self.h1.clone()
+    }
+}
+
+impl<S: SecurityParams> Prover<S> {
     pub fn get_param(&self) -> Param {
         self.param
     }
+
+    pub fn get_instance(&self) -> &Instance<S> {
+        &self.instance
+    }
+
     /// Create a new prover with a random witness-instance pair,
     /// generated using `rng` according to parameters `param`.
     /// Internally, the master seed is also sampled from the `rng`.
     pub fn new<R: RngCore + CryptoRng>(rng: &mut R, param: Param) -> Self {
         let (witness, instance) = new_witness_instance(rng, param);
-        let mut mseed = [0u8; BLOCK_SIZE];
+        let mut mseed = Key::<S>::default();
         rng.fill_bytes(&mut mseed);
         Self::from_witness_instance_unchecked(witness, instance, mseed, param)
     }
@@ -92,8 +289,8 @@ impl Prover {
     /// an error if the check fails.
     pub fn from_witness_instance(
         witness: Witness,
-        instance: Instance,
-        mseed: [u8; BLOCK_SIZE],
+        instance: Instance<S>,
+        mseed: Key<S>,
         param: Param,
     ) -> Result<Self, InternalError> {
         sanity_check(&witness, &instance, param)?;
@@ -102,169 +299,334 @@ impl Prover {
         ))
     }
 
-    fn from_witness_instance_unchecked(
+    pub(crate) fn from_witness_instance_unchecked(
         witness: Witness,
-        instance: Instance,
-        mseed: [u8; BLOCK_SIZE],
+        instance: Instance<S>,
+        mseed: Key<S>,
         param: Param,
     ) -> Self {
-        let iv = hash_witness_instance(&witness, &instance);
+        let iv = hash_instance(&instance);
         Prover {
             witness,
             instance,
             mseed,
             iv,
             param,
+            mode: ProverMode::default(),
         }
     }
 
+    /// Set how much of the instances is kept between the steps.
+    pub fn with_mode(mut self, mode: ProverMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn get_mode(&self) -> ProverMode {
+        self.mode
+    }
+
+    /// Replace the master seed by a fresh one derived from it,
+    /// it is used to restart the protocol after an abort.
+    pub fn restart(&mut self) {
+        let (mseed, _) = prg_double::<S>(&self.mseed, &iv_from_label::<S>(&IV_RESTART));
+        self.mseed = mseed;
+    }
+
     /// Run the first step of the protocol and output the prover state.
-    pub fn step1(&self) -> ProverState {
+    pub fn step1(&self) -> ProverState<S> {
         let mut h1s = Vec::with_capacity(self.param.cnc_param);
         let mut state = ProverState::new();
 
-        let mseeds_inner = prg_tree(&self.mseed, &self.iv, self.param.cnc_param);
-        debug_assert_eq!(mseeds_inner.len(), self.param.cnc_param);
-        for mseed_inner in mseeds_inner {
-            let rs = prg_bin(&mseed_inner, &self.iv, self.param.ssp_dimension);
-            let seeds_rhos = prg_tree(&mseed_inner, &self.iv, self.param.party_count * 2);
-            let (seeds, rhos): (Vec<_>, Vec<_>) = seeds_rhos
-                .chunks_exact(2)
-                .map(|arr| (arr[0], Opening::new(arr[1])))
-                .unzip();
-            debug_assert_eq!(seeds.len(), self.param.party_count);
-            debug_assert_eq!(rhos.len(), self.param.party_count);
-
-            let r_shares: Vec<Vec<u64>> = seeds
-                .iter()
-                .map(|seed| {
-                    prg_u64(seed, &self.iv, self.param.ssp_dimension)
-                        .iter()
-                        .map(|x| x % (1 << self.param.abort_param as u64))
-                        .collect()
-                })
-                .collect();
-
-            let coms: Vec<_> = seeds
-                .iter()
-                .zip(rhos.iter())
-                .map(|(seed, rho)| commit(seed, &rho))
-                .collect();
+        let tree = SeedTree::<S>::expand(&self.mseed, &self.iv, self.param.cnc_param);
+        debug_assert_eq!(tree.leaves().len(), self.param.cnc_param);
+        for mseed_inner in tree.leaves() {
+            // in the lean mode only h1 and the master seed are kept,
+            // the instance is recomputed on demand
+            let instance = CncInstance::<S>::new(mseed_inner, &self.iv, self.param);
+            h1s.push(instance.get_h1());
+            state.push_inner(ProverStateInner {
+                mseed_inner: mseed_inner.clone(),
+                h1: instance.get_h1(),
+            });
+            if self.mode == ProverMode::Full {
+                state.instances.push(instance);
+            }
+        }
+        let h = hash2::<S>(&h1s);
+        state.set_h(h);
+        state
+    }
 
-            // sum over the N vectors
-            let r_shares_sum: Vec<_> = r_shares
-                .iter()
-                .fold(vec![0u64; self.param.ssp_dimension], |acc, x| {
-                    acc.into_iter().zip(x).map(|(a, b)| a + b).collect()
-                });
-            let delta_rs: Vec<_> = rs
+    /// Check that a saved `state` belongs to this prover
+    /// and output the step at which the session resumes.
+    /// The master seeds must be the ones of this prover, h must be the hash of h1s
+    /// and J and L must be valid. The instances are not expanded again,
+    /// so the state is in the lean mode after it is deserialized.
+    /// A state saved before J or L is recorded must not be resumed
+    /// if it may have been answered since, see `Prover::record_chal1`
+    /// and `Prover::record_chal2`.
+    pub fn resume(&self, state: &ProverState<S>) -> Result<ResumeStep, InternalError> {
+        let tree = SeedTree::<S>::expand(&self.mseed, &self.iv, self.param.cnc_param);
+        if state.step1_state.len() != self.param.cnc_param
+            || state
+                .step1_state
                 .iter()
-                .zip(&r_shares_sum)
-                .map(|(r, share)| (*r as u64).wrapping_sub(*share))
-                .collect();
-
-            let h1 = hash1(&delta_rs, &coms);
-            h1s.push(h1);
-
-            // Create the state object
-            let inner = ProverStateInner {
-                mseed_inner,
-                rs,
-                seeds: seeds
-                    .into_iter()
-                    .map(|seed| WrapperArray::new(seed))
-                    .collect(),
-                rhos,
-                r_shares,
-                coms,
-                r_shares_sum,
-                delta_rs,
-                h1,
+                .zip(tree.leaves())
+                .any(|(inner, mseed_inner)| inner.mseed_inner != *mseed_inner)
+        {
+            return Err(InternalError::BadProverState);
+        }
+        let h1s: Vec<_> = state
+            .step1_state
+            .iter()
+            .map(|inner| inner.h1.clone())
+            .collect();
+        if hash2::<S>(&h1s) != state.h {
+            return Err(InternalError::BadProverState);
+        }
+        if state.chal1.is_empty() {
+            return if state.chal2.is_empty() {
+                Ok(ResumeStep::Step2)
+            } else {
+                Err(InternalError::BadProverState)
             };
-            state.push_inner(inner);
         }
-        let h = hash2(&h1s);
-        state.set_h(h);
-        // TODO: possibly we need to store the state in the Prover object
+        self.check_chal1(&state.chal1)?;
+        if !state.chal2.is_empty() {
+            self.check_chal2(&state.chal2)?;
+        }
+        Ok(ResumeStep::Step3)
+    }
+
+    /// Check that J is tau distinct indices in [M].
+    fn check_chal1(&self, chal1: &[usize]) -> Result<(), InternalError> {
+        if chal1.len() != self.param.rep_param {
+            return Err(InternalError::BadChallengeLength);
+        }
+        let mut in_chal1 = vec![false; self.param.cnc_param];
+        for e in chal1 {
+            if *e >= self.param.cnc_param {
+                return Err(InternalError::ChallengeOutOfRange);
+            }
+            if in_chal1[*e] {
+                return Err(InternalError::DuplicateChallenge);
+            }
+            in_chal1[*e] = true;
+        }
+        Ok(())
+    }
+
+    /// Check that L is tau indices in [N], repetition is allowed.
+    fn check_chal2(&self, ells: &[usize]) -> Result<(), InternalError> {
+        if ells.len() != self.param.rep_param {
+            return Err(InternalError::BadChallengeLength);
+        }
+        if ells.iter().any(|ell| *ell >= self.param.party_count) {
+            return Err(InternalError::ChallengeOutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Expand the instances in J unless they are kept in the state.
+    fn expand_chal1<'a>(&self, state: &'a ProverState<S>) -> Vec<Cow<'a, CncInstance<S>>> {
         state
+            .chal1
+            .iter()
+            .map(|e| match state.instances.get(*e) {
+                Some(instance) => Cow::Borrowed(instance),
+                None => Cow::Owned(CncInstance::new(
+                    &state.step1_state[*e].mseed_inner,
+                    &self.iv,
+                    self.param,
+                )),
+            })
+            .collect()
     }
 
-    pub fn step2(
+    /// Compute the masked witness `xs_tilde` and the broadcast shares of `t`
+    /// of every party for one C&C instance.
+    fn xs_tilde_t_shares(&self, inner: &CncInstance<S>) -> (Vec<u8>, Vec<u64>) {
+        let xs_tilde: Vec<_> = self
+            .witness
+            .0
+            .iter()
+            .zip(inner.rs.iter())
+            .map(|(a, b)| a ^ b)
+            .collect();
+
+        let t_shares = inner
+            .r_shares
+            .iter()
+            .enumerate()
+            .map(|(i, r_share)| {
+                // only the first party holds the public delta_rs
+                let delta_rs = if i == 0 {
+                    Some(inner.delta_rs.as_slice())
+                } else {
+                    None
+                };
+                compute_t_share(&self.instance, &xs_tilde, r_share, delta_rs)
+            })
+            .collect();
+        (xs_tilde, t_shares)
+    }
+
+    /// Record the challenge J in the state, J must be tau distinct indices in [M].
+    /// Once J is recorded, a different J is rejected with
+    /// `InternalError::ConflictingChallenge`, since answering two different J
+    /// reveals the master seeds of the opened instances, and so the witness.
+    /// A session that is saved to be resumed must save the state
+    /// after J is recorded and before the answer of `Prover::step2` is sent,
+    /// otherwise the resumed state does not know which J was answered.
+    pub fn record_chal1(
         &self,
-        state: &ProverState,
-        chal1: &Vec<usize>,
-    ) -> Result<([u8; DIGEST_SIZE], Vec<[u8; BLOCK_SIZE]>), InternalError> {
+        state: &mut ProverState<S>,
+        chal1: &[usize],
+    ) -> Result<(), InternalError> {
         // check length of chalJ
         if chal1.len() != self.param.rep_param {
             return Err(InternalError::BadChallengeLength);
         }
+        if state.step1_state.len() != self.param.cnc_param {
+            return Err(InternalError::BadProverState);
+        }
+        // check that J is a subset of [M] without repetition
+        self.check_chal1(chal1)?;
+        if !state.chal1.is_empty() && state.chal1 != chal1 {
+            return Err(InternalError::ConflictingChallenge);
+        }
+        state.set_chal1(chal1.to_vec());
+        Ok(())
+    }
 
-        // TODO check that J \subset [M]
+    /// Run the second step of the protocol on the challenge J,
+    /// J is recorded first, see `Prover::record_chal1`,
+    /// so the same J can be answered again but no other J.
+    /// The master seeds of the instances that are not in J are revealed
+    /// by the seed tree of the master seeds punctured at J,
+    /// so none of the seeds of the instances in J leave the prover.
+    pub fn step2(
+        &self,
+        state: &mut ProverState<S>,
+        chal1: &[usize],
+    ) -> Result<(DigestOutput<S>, Vec<Key<S>>), InternalError> {
+        // the challenge is needed again in step3
+        self.record_chal1(state, chal1)?;
+        let instances = self.expand_chal1(state);
 
-        let h_primes = chal1.iter().map(|e| {
-            let xs_tilde: Vec<_> = self
-                .witness
-                .0
-                .iter()
-                .zip(state.step1_state[*e].rs.iter())
-                .map(|(a, b)| a ^ b)
-                .collect();
-
-            let t_shares = state.step1_state[*e].r_shares.iter().map(|r_share| {
-                // x_share is [x], per c&c and per party i
-                let x_share = xs_tilde.iter().zip(r_share).map(|(x_tilde, r_share)| {
-                    // TODO check if we should be using wrapping_mul
-                    u64::from(1u8 - x_tilde)
-                        .wrapping_mul(*r_share)
-                        .wrapping_add(u64::from(*x_tilde).wrapping_mul(1u64.wrapping_sub(*r_share)))
-                });
-                let t_share: u64 = self
-                    .instance
-                    .weights
-                    .iter()
-                    .zip(x_share)
-                    .map(|(w, x)| x.wrapping_mul(*w))
-                    .fold(0, |acc, s| acc.wrapping_add(s));
-                t_share
-            });
+        let h_primes = instances.iter().map(|instance| {
+            let (xs_tilde, t_shares) = self.xs_tilde_t_shares(instance);
 
             // hash shares and xs_tilde
-            // TODO: remove collect and hash incrementally
-            let h_prime = hash3(&xs_tilde, t_shares);
-            h_prime
+            hash3::<S, _>(&xs_tilde, t_shares.into_iter())
         });
 
         // hash all the h_primes
-        let h_prime = hash4(h_primes);
+        let h_prime = hash4::<S, _>(h_primes);
 
-        // find the mseeds that are not in chalJ
-        let mseeds: Vec<_> = chal1
-            .iter()
-            .map(|e| {
-                // TODO: this is wrong, need e \notin J
-                state.step1_state[*e].mseed_inner
-            })
-            .collect();
-        Ok((h_prime, mseeds))
+        // the verifier recomputes h1 of the instances that are not in chalJ
+        // from the master seeds revealed by the path
+        let mseed_path =
+            SeedTree::<S>::expand(&self.mseed, &self.iv, self.param.cnc_param).open_all_but(chal1);
+
+        Ok((h_prime, mseed_path))
     }
 
-    pub fn step3(&self, state: &ProverState, ells: &[usize]) {
-        // not implemented yet
+    /// Record the challenge L in the state, L must be tau indices in [N]
+    /// and J must be recorded. Once L is recorded, a different L is rejected with
+    /// `InternalError::ConflictingChallenge`, since answering another L
+    /// after an abort opens the share that causes the abort, and so reveals r.
+    /// A session that is saved to be resumed must save the state
+    /// after L is recorded and before the answer of `Prover::step3` is sent.
+    pub fn record_chal2(
+        &self,
+        state: &mut ProverState<S>,
+        ells: &[usize],
+    ) -> Result<(), InternalError> {
+        // check length of chalL, and that step2 was executed
+        if ells.len() != self.param.rep_param || state.chal1.len() != self.param.rep_param {
+            return Err(InternalError::BadChallengeLength);
+        }
+        self.check_chal2(ells)?;
+        if state.step1_state.len() != self.param.cnc_param {
+            return Err(InternalError::BadProverState);
+        }
+        self.check_chal1(&state.chal1)?;
+        if !state.chal2.is_empty() && state.chal2 != ells {
+            return Err(InternalError::ConflictingChallenge);
+        }
+        state.set_chal2(ells.to_vec());
+        Ok(())
+    }
+
+    /// Run the third step of the protocol on the challenge L,
+    /// L is recorded first, see `Prover::record_chal2`.
+    /// For every repetition in J, all parties except the unopened party ell
+    /// are opened, together with the commitment and the broadcast share of ell.
+    /// The opened parties are revealed by the seed tree punctured at ell,
+    /// which takes about log N nodes instead of N - 1 seeds and openings.
+    /// `InternalError::Abort` is returned if an instance in J aborts given ell,
+    /// see `ProverState::abort_count`, then the protocol has to be restarted,
+    /// see `Prover::restart`.
+    pub fn step3(
+        &self,
+        state: &mut ProverState<S>,
+        ells: &[usize],
+    ) -> Result<Vec<Step3Inner<S>>, InternalError> {
+        self.record_chal2(state, ells)?;
+        let instances = self.expand_chal1(state);
+
+        // the abort only depends on the share of the unopened party,
+        // so it is decided once L is known
+        let abort_count = instances
+            .iter()
+            .zip(ells)
+            .filter(|(instance, ell)| instance.aborts(**ell, self.param))
+            .count();
+
+        let out = (abort_count == 0).then(|| {
+            state
+                .chal1
+                .iter()
+                .zip(instances)
+                .zip(ells)
+                .map(|((e, instance), ell)| {
+                    let (xs_tilde, t_shares) = self.xs_tilde_t_shares(&instance);
+                    // the tree is cheap to recompute so it is not kept in the state
+                    let path = CncInstance::<S>::party_tree(
+                        &state.step1_state[*e].mseed_inner,
+                        &self.iv,
+                        self.param,
+                    )
+                    .open_all_but(&[*ell]);
+                    Step3Inner {
+                        path,
+                        com: instance.coms[*ell].inner.clone(),
+                        delta_rs: instance.delta_rs.clone(),
+                        xs_tilde,
+                        t_share: t_shares[*ell],
+                    }
+                })
+                .collect()
+        });
+        state.abort_count = abort_count;
+        out.ok_or(InternalError::Abort)
     }
 }
 
 // interactive prover
-pub struct IProver {
-    prover: Prover,
-    tx: Sender<ProverMsg>,
+pub struct IProver<S: SecurityParams = L1> {
+    prover: Prover<S>,
+    tx: Sender<ProverMsg<S>>,
     rx: Receiver<VerifierMsg>,
 }
 
-impl IProver {
+impl<S: SecurityParams> IProver<S> {
     pub fn new<R: RngCore + CryptoRng>(
         rng: &mut R,
         param: Param,
-        tx: Sender<ProverMsg>,
+        tx: Sender<ProverMsg<S>>,
         rx: Receiver<VerifierMsg>,
     ) -> Self {
         Self {
@@ -274,55 +636,530 @@ impl IProver {
         }
     }
 
-    pub fn from_prover(prover: Prover, tx: Sender<ProverMsg>, rx: Receiver<VerifierMsg>) -> Self {
+    pub fn from_prover(
+        prover: Prover<S>,
+        tx: Sender<ProverMsg<S>>,
+        rx: Receiver<VerifierMsg>,
+    ) -> Self {
         Self { prover, tx, rx }
     }
 
-    pub fn blocking_run(&mut self) -> Result<(), InternalError> {
-        let state = self.prover.step1();
-        self.tx.send(ProverMsg::Step1(state.h))?;
-
-        // receive the first challenge J
-        let chal1 = match self.rx.recv()? {
-            VerifierMsg::Step1(c) => c,
-            _ => return Err(InternalError::ProtocolError),
-        };
-
-        let (h_prime, mseeds) = self.prover.step2(&state, &chal1)?;
-        self.tx.send(ProverMsg::Step2((h_prime, mseeds)))?;
-
-        // receive the second challenge L
-        let chal2 = match self.rx.recv()? {
-            VerifierMsg::Step2(c) => c,
-            _ => return Err(InternalError::ProtocolError),
-        };
-
-        self.prover.step3(&state, &chal2);
-
-        Ok(())
+    /// Run the handshake and the protocol and output the verdict of the verifier,
+    /// if an instance in J aborts given L the prover restarts by sending a new first message.
+    pub fn blocking_run(&mut self) -> Result<bool, InternalError> {
+        let mut session = ProverSession::new(&mut self.prover);
+        let mut msg = session.start();
+        while let Some(prover_msg) = msg.take() {
+            self.tx.send(prover_msg)?;
+            msg = session.handle(self.rx.recv()?);
+        }
+        session.finish()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::security::{digest_size, key_size};
     use crossbeam::channel::unbounded;
     use rand_chacha::ChaChaRng;
     use rand_core::SeedableRng;
     use std::thread;
 
+    type Step2Msg<S> = (DigestOutput<S>, Vec<Key<S>>);
+
+    /// Run the three steps on the challenges J and L,
+    /// the prover restarts until no instance in J aborts given L.
+    fn run_steps<S: SecurityParams>(
+        prover: &mut Prover<S>,
+        chal1: &[usize],
+        chal2: &[usize],
+    ) -> (ProverState<S>, Step2Msg<S>, Vec<Step3Inner<S>>) {
+        loop {
+            let mut state = prover.step1();
+            let step2 = prover.step2(&mut state, chal1).unwrap();
+            match prover.step3(&mut state, chal2) {
+                Err(InternalError::Abort) => prover.restart(),
+                res => return (state, step2, res.unwrap()),
+            }
+        }
+    }
+
+    fn check_step3<S: SecurityParams>() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<S>::new(&mut rng, param);
+
+        let chal1: Vec<_> = (0..param.rep_param).collect();
+        let chal2: Vec<_> = (0..param.rep_param)
+            .map(|i| i % param.party_count)
+            .collect();
+        let (mut state, _, openings) = run_steps(&mut prover, &chal1, &chal2);
+        assert_eq!(openings.len(), param.rep_param);
+        assert_eq!(state.abort_count(), 0);
+
+        for ((e, ell), opening) in chal1.iter().zip(&chal2).zip(&openings) {
+            let mseed_inner = &state.step1_state[*e].mseed_inner;
+            let instance = CncInstance::<S>::new(mseed_inner, &prover.iv, param);
+            // the co-path of ell in a tree with 4 leaves
+            assert_eq!(opening.path.len(), 2);
+            assert_eq!(opening.com, instance.coms[*ell].inner);
+
+            // the path reveals the leaves of all parties except ell
+            let leaves =
+                SeedTree::<S>::reconstruct(&opening.path, &[*ell], &prover.iv, param.party_count)
+                    .unwrap();
+            let tree = CncInstance::<S>::party_tree(mseed_inner, &prover.iv, param);
+            for (i, leaf) in leaves.iter().enumerate() {
+                if i == *ell {
+                    assert_eq!(*leaf, None);
+                } else {
+                    assert_eq!(leaf.as_ref(), Some(&tree.leaves()[i]));
+                }
+            }
+
+            // the broadcast shares of all parties must sum to t
+            let (_, t_shares) = prover.xs_tilde_t_shares(&instance);
+            assert_eq!(t_shares[*ell], opening.t_share);
+            let t = t_shares.iter().fold(0u64, |acc, s| acc.wrapping_add(*s));
+            assert_eq!(t, prover.instance.t);
+        }
+
+        // the length of chalL must be tau
+        assert_eq!(
+            prover.step3(&mut state, &chal2[1..]),
+            Err(InternalError::BadChallengeLength)
+        );
+    }
+
+    #[test]
+    fn test_step3() {
+        check_step3::<L1>();
+        check_step3::<L5>();
+    }
+
+    fn check_step2_hides_challenged<S: SecurityParams>() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<S>::new(&mut rng, param);
+
+        // spread J over the tree
+        let chal1: Vec<_> = (0..param.rep_param).map(|i| i * 4 + 1).collect();
+        let chal2 = vec![0; param.rep_param];
+        let (state, (h_prime, mseed_path), openings) = run_steps(&mut prover, &chal1, &chal2);
+        assert!(mseed_path.len() <= SeedTree::<S>::max_open_size(param.cnc_param, param.rep_param));
+        assert!(!mseed_path.contains(&prover.mseed));
+
+        // the path reveals the master seeds of the instances not in J and nothing else
+        let leaves =
+            SeedTree::<S>::reconstruct(&mseed_path, &chal1, &prover.iv, param.cnc_param).unwrap();
+        for (e, leaf) in leaves.iter().enumerate() {
+            let mseed_inner = &state.step1_state[e].mseed_inner;
+            if chal1.contains(&e) {
+                assert_eq!(*leaf, None);
+                assert!(!mseed_path.contains(mseed_inner));
+            } else {
+                assert_eq!(leaf.as_ref(), Some(mseed_inner));
+            }
+        }
+
+        // no challenged master seed or party seed appears in the messages
+        let mut bytes = bincode::serialize(&ProverMsg::<S>::Step2((h_prime, mseed_path))).unwrap();
+        bytes.extend(bincode::serialize(&ProverMsg::Step3(openings)).unwrap());
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+        assert!(!contains(&prover.mseed));
+        for e in &chal1 {
+            let mseed_inner = &state.step1_state[*e].mseed_inner;
+            let tree = CncInstance::<S>::party_tree(mseed_inner, &prover.iv, param);
+            let (seed, _) =
+                prg_pair::<S, S::KeySize, S::OpeningSize>(&tree.leaves()[0], &prover.iv);
+            assert!(!contains(mseed_inner));
+            assert!(!contains(&tree.leaves()[0]));
+            assert!(!contains(&seed));
+        }
+    }
+
+    #[test]
+    fn test_step2_hides_challenged() {
+        check_step2_hides_challenged::<L1>();
+        check_step2_hides_challenged::<L5>();
+    }
+
+    fn check_openings_hide_r<S: SecurityParams>() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<S>::new(&mut rng, param);
+        let chal1: Vec<_> = (0..param.rep_param).collect();
+
+        let bits = |node: &Key<S>| -> Vec<u8> {
+            node.iter()
+                .flat_map(|b| (0u8..8).map(move |shift| (b >> shift) & 1))
+                .collect()
+        };
+        for ell in 0..param.party_count {
+            let chal2 = vec![ell; param.rep_param];
+            let (state, _, openings) = run_steps(&mut prover, &chal1, &chal2);
+            for (e, opening) in chal1.iter().zip(&openings) {
+                let instance =
+                    CncInstance::<S>::new(&state.step1_state[*e].mseed_inner, &prover.iv, param);
+                // xs_tilde is the witness masked by r, so no revealed node
+                // may be a part of r or expand into r
+                for node in &opening.path {
+                    let node_bits = bits(node);
+                    assert!(instance
+                        .rs
+                        .chunks(node_bits.len())
+                        .all(|chunk| chunk != &node_bits[..chunk.len()]));
+                    assert_ne!(
+                        prg_bin::<S>(node, &prover.iv, param.ssp_dimension),
+                        instance.rs
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_openings_hide_r() {
+        check_openings_hide_r::<L1>();
+        check_openings_hide_r::<L5>();
+    }
+
+    #[test]
+    fn test_bad_challenges() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let mut state = prover.step1();
+
+        // J must be in [M] without repetition
+        let mut chal1: Vec<_> = (0..param.rep_param).collect();
+        chal1[0] = param.cnc_param;
+        assert_eq!(
+            prover.step2(&mut state, &chal1),
+            Err(InternalError::ChallengeOutOfRange)
+        );
+        chal1[0] = 1;
+        assert_eq!(
+            prover.step2(&mut state, &chal1),
+            Err(InternalError::DuplicateChallenge)
+        );
+
+        // L cannot be answered before J
+        let mut chal2 = vec![0; param.rep_param];
+        assert_eq!(
+            prover.step3(&mut state, &chal2),
+            Err(InternalError::BadChallengeLength)
+        );
+
+        // a rejected J leaves the state unchanged
+        chal1[0] = 0;
+        let res = prover.step2(&mut state, &chal1);
+        assert!(res.is_ok());
+
+        // J can only be answered once, but the same J can be answered again
+        let other_chal1: Vec<_> = (1..=param.rep_param).collect();
+        assert_eq!(
+            prover.step2(&mut state, &other_chal1),
+            Err(InternalError::ConflictingChallenge)
+        );
+        assert_eq!(prover.step2(&mut state, &chal1), res);
+
+        // L must be in [N]
+        chal2[param.rep_param - 1] = param.party_count;
+        assert_eq!(
+            prover.step3(&mut state, &chal2),
+            Err(InternalError::ChallengeOutOfRange)
+        );
+
+        // a rejected L leaves the state unchanged, but L may abort
+        chal2[param.rep_param - 1] = param.party_count - 1;
+        let res = prover.step3(&mut state, &chal2);
+        assert!(res.is_ok() || res == Err(InternalError::Abort));
+
+        // L can only be answered once, but the same L can be answered again
+        let other_chal2 = vec![1; param.rep_param];
+        assert_eq!(
+            prover.step3(&mut state, &other_chal2),
+            Err(InternalError::ConflictingChallenge)
+        );
+        assert_eq!(prover.step3(&mut state, &chal2), res);
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        // the expanded instances of the full mode are not saved, see test_modes
+        let prover = Prover::<L1>::new(&mut rng, param).with_mode(ProverMode::Lean);
+        let chal1: Vec<_> = (0..param.rep_param).collect();
+
+        // save the state after step1, and again once J is recorded
+        let mut state = prover.step1();
+        let saved = bincode::serialize(&state).unwrap();
+        prover.record_chal1(&mut state, &chal1).unwrap();
+        let recorded = bincode::serialize(&state).unwrap();
+        let step2_msg = prover.step2(&mut state, &chal1).unwrap();
+        // only the master seeds and h1s are kept, they are hex encoded
+        let inner_size = 2 * (key_size::<L1>() + digest_size::<L1>()) + 16;
+        assert!(saved.len() < param.cnc_param * inner_size + 256);
+
+        // resume at step2 in a new process
+        let saved_prover = serde_json::to_string(&prover).unwrap();
+        let resumed_prover: Prover<L1> = serde_json::from_str(&saved_prover).unwrap();
+        let mut resumed: ProverState<L1> = bincode::deserialize(&saved).unwrap();
+        assert_eq!(resumed_prover.resume(&resumed), Ok(ResumeStep::Step2));
+        assert_eq!(
+            resumed_prover.step2(&mut resumed, &chal1),
+            Ok(step2_msg.clone())
+        );
+        assert_eq!(resumed, state);
+
+        // a state saved once J is recorded only answers that J,
+        // another J would reveal the master seeds of the opened instances
+        let mut resumed: ProverState<L1> = bincode::deserialize(&recorded).unwrap();
+        assert_eq!(resumed_prover.resume(&resumed), Ok(ResumeStep::Step3));
+        let other_chal1: Vec<_> = (1..=param.rep_param).collect();
+        assert_eq!(
+            resumed_prover.step2(&mut resumed, &other_chal1),
+            Err(InternalError::ConflictingChallenge)
+        );
+        assert_eq!(resumed_prover.step2(&mut resumed, &chal1), Ok(step2_msg));
+
+        // resume at step3
+        let saved = serde_json::to_string(&state).unwrap();
+        let mut resumed: ProverState<L1> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(resumed_prover.resume(&resumed), Ok(ResumeStep::Step3));
+        let chal2 = vec![1; param.rep_param];
+        assert_eq!(
+            resumed_prover.step3(&mut resumed, &chal2),
+            prover.step3(&mut state, &chal2)
+        );
+
+        // a state saved once L is recorded only answers that L
+        let saved = serde_json::to_string(&state).unwrap();
+        let mut resumed: ProverState<L1> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(resumed_prover.resume(&resumed), Ok(ResumeStep::Step3));
+        assert_eq!(
+            resumed_prover.step3(&mut resumed, &vec![0; param.rep_param]),
+            Err(InternalError::ConflictingChallenge)
+        );
+        let mut bad_chal2 = resumed.clone();
+        bad_chal2.chal2[0] = param.party_count;
+        assert_eq!(
+            resumed_prover.resume(&bad_chal2),
+            Err(InternalError::ChallengeOutOfRange)
+        );
+
+        // so is a state with a modified h
+        let mut bad_h = resumed.clone();
+        bad_h.h[0] ^= 1;
+        assert_eq!(
+            resumed_prover.resume(&bad_h),
+            Err(InternalError::BadProverState)
+        );
+
+        // the state of another prover is rejected
+        let other = Prover::<L1>::new(&mut rng, param);
+        assert_eq!(other.resume(&state), Err(InternalError::BadProverState));
+        state.step1_state.pop();
+        assert_eq!(prover.resume(&state), Err(InternalError::BadProverState));
+        assert_eq!(
+            prover.step3(&mut state, &chal2),
+            Err(InternalError::BadProverState)
+        );
+
+        // the witness is checked when the prover is deserialized
+        let mut json: serde_json::Value = serde_json::from_str(&saved_prover).unwrap();
+        json["witness"][0] = (1 - json["witness"][0].as_u64().unwrap()).into();
+        assert!(serde_json::from_value::<Prover<L1>>(json).is_err());
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let full = Prover::<L1>::new(&mut rng, param);
+        assert_eq!(full.get_mode(), ProverMode::Full);
+        let lean = Prover::from_witness_instance(
+            full.witness.clone(),
+            full.instance.clone(),
+            full.mseed,
+            param,
+        )
+        .unwrap()
+        .with_mode(ProverMode::Lean);
+
+        // the modes only differ in what is kept in the state
+        let mut lean_state = lean.step1();
+        let mut full_state = full.step1();
+        assert_eq!(lean_state.get_h(), full_state.get_h());
+        assert!(lean_state.instances.is_empty());
+        assert_eq!(full_state.instances.len(), param.cnc_param);
+
+        let chal1: Vec<_> = (0..param.rep_param).collect();
+        let chal2 = vec![2; param.rep_param];
+        assert_eq!(
+            lean.step2(&mut lean_state, &chal1),
+            full.step2(&mut full_state, &chal1)
+        );
+        assert_eq!(
+            lean.step3(&mut lean_state, &chal2),
+            full.step3(&mut full_state, &chal2)
+        );
+
+        // the expanded instances are not saved
+        let saved = bincode::serialize(&full_state).unwrap();
+        assert_eq!(saved, bincode::serialize(&lean_state).unwrap());
+        let resumed: ProverState<L1> = bincode::deserialize(&saved).unwrap();
+        assert!(resumed.instances.is_empty());
+        assert_eq!(full.resume(&resumed), Ok(ResumeStep::Step3));
+    }
+
+    #[test]
+    fn test_abort_rate() {
+        let mut rng = ChaChaRng::from_entropy();
+        // every share is at the boundary with probability 2^-8,
+        // so about 6% of the instances abort given ell
+        let param = Param::new(16, 4, 2000, 8, 1).unwrap();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let state = prover.step1();
+
+        let mut boundary_count = 0;
+        let mut abort_count = 0;
+        for (e, inner) in state.step1_state.iter().enumerate() {
+            let instance = CncInstance::<L1>::new(&inner.mseed_inner, &prover.iv, param);
+            let ell = e % param.party_count;
+            let mut aborts = false;
+            for (i, r_share) in instance.r_shares.iter().enumerate() {
+                for (share, r) in r_share.iter().zip(&instance.rs) {
+                    if *share == if *r == 1 { 0 } else { 255 } {
+                        boundary_count += 1;
+                        // only the share of the unopened party counts
+                        aborts |= i == ell;
+                    }
+                }
+            }
+            assert_eq!(instance.aborts(ell, param), aborts);
+            abort_count += usize::from(aborts);
+        }
+
+        // the rate of the boundary shares is 2^-A
+        let share_count = param.cnc_param * param.party_count * param.ssp_dimension;
+        let share_rate = boundary_count as f64 / share_count as f64;
+        assert!((share_rate - 1.0 / 256.0).abs() < 1e-3);
+
+        // the rate of the aborts is the one given by the analysis
+        let abort_rate = abort_count as f64 / param.cnc_param as f64;
+        assert!((abort_rate - param.abort_probability()).abs() < 0.03);
+    }
+
+    #[test]
+    fn test_opened_shares_hide_r() {
+        let mut rng = ChaChaRng::from_entropy();
+        // with A = 4 a share is at the boundary with probability 1/4
+        let param = Param::new(4, 4, 4000, 2, 1).unwrap();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let state = prover.step1();
+
+        // the values of the opened shares for r = 0 and r = 1
+        // in the instances that do not abort
+        let mut opened = [[0usize; 4]; 2];
+        for (e, inner) in state.step1_state.iter().enumerate() {
+            let instance = CncInstance::<L1>::new(&inner.mseed_inner, &prover.iv, param);
+            let ell = e % param.party_count;
+            if instance.aborts(ell, param) {
+                continue;
+            }
+            for (i, r_share) in instance.r_shares.iter().enumerate() {
+                for (share, r) in r_share.iter().zip(&instance.rs) {
+                    if i == ell {
+                        // the revealed r - s takes the same values for r = 0 and r = 1
+                        let diff = i64::from(*r) - *share as i64;
+                        assert!((-2..=0).contains(&diff));
+                    } else {
+                        opened[usize::from(*r)][*share as usize] += 1;
+                    }
+                }
+            }
+        }
+
+        // the opened shares are uniform in [0, A) whatever r is,
+        // in particular 0 is opened when r = 1 and A - 1 when r = 0
+        for counts in opened {
+            let total: usize = counts.iter().sum();
+            for count in counts {
+                assert!((count as f64 / total as f64 - 0.25).abs() < 0.03);
+            }
+        }
+    }
+
+    #[test]
+    fn test_abort() {
+        let mut rng = ChaChaRng::from_entropy();
+        // with log A = 1 almost every instance aborts
+        let param = Param::new(16, 4, 8, 1, 2).unwrap();
+        let mut prover = Prover::<L1>::new(&mut rng, param);
+        let mut state = prover.step1();
+
+        // the abort is only known once L is
+        let chal1 = vec![0, 1];
+        assert!(prover.step2(&mut state, &chal1).is_ok());
+        assert_eq!(state.abort_count(), 0);
+        assert_eq!(prover.step3(&mut state, &[0, 0]), Err(InternalError::Abort));
+        assert!(state.abort_count() > 0);
+        let mut stats = AbortStats::default();
+        stats.record(&state);
+        assert_eq!(stats.instance_count, param.rep_param);
+        assert_eq!(stats.abort_count, state.abort_count());
+
+        // the aborted state cannot be used anymore,
+        // another L would open the share that causes the abort
+        assert_eq!(prover.step3(&mut state, &[0, 0]), Err(InternalError::Abort));
+        assert_eq!(
+            prover.step3(&mut state, &[1, 1]),
+            Err(InternalError::ConflictingChallenge)
+        );
+
+        // the restart uses a fresh master seed
+        let h = state.get_h();
+        prover.restart();
+        assert_ne!(prover.step1().get_h(), h);
+    }
+
+    #[test]
+    fn test_iprover_bad_chal1() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let (tx_p, rx_p) = unbounded();
+        let (tx_v, rx_v) = unbounded();
+        let mut iprover = IProver::<L1>::new(&mut rng, param, tx_p, rx_v);
+        let handle = thread::spawn(move || iprover.blocking_run());
+        let _ = rx_p.recv().unwrap();
+        tx_v.send(VerifierMsg::Hello(Ok(()))).unwrap();
+        let _ = rx_p.recv().unwrap();
+
+        // a malicious verifier must not be able to crash the prover
+        let chal1 = vec![usize::MAX; param.rep_param];
+        tx_v.send(VerifierMsg::Step1(chal1)).unwrap();
+        let res = handle.join().unwrap();
+        assert_eq!(res, Err(InternalError::ChallengeOutOfRange));
+    }
+
     #[test]
     fn test_iprover_wrong_chal1() {
         let mut rng = ChaChaRng::from_entropy();
         let param = Param::default();
         let (tx_p, rx_p) = unbounded();
         let (tx_v, rx_v) = unbounded();
-        let mut iprover = IProver::new(&mut rng, param, tx_p, rx_v);
+        let mut iprover = IProver::<L1>::new(&mut rng, param, tx_p, rx_v);
 
         // run the prover in a thread
         let handle = thread::spawn(move || iprover.blocking_run());
 
-        // we should receive something from the prover automatically
+        // we should receive the handshake and h from the prover automatically
+        let _ = rx_p.recv().unwrap();
+        tx_v.send(VerifierMsg::Hello(Ok(()))).unwrap();
         let _ = rx_p.recv().unwrap();
 
         // then sending the wrong verification message should fail
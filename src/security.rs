@@ -0,0 +1,177 @@
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+
+use generic_array::{
+    typenum::{IsGreaterOrEqual, True, Unsigned, U16, U32, U64},
+    ArrayLength, GenericArray,
+};
+
+use crate::hash::{ProtocolHash, Sha3_256, Sha3_512};
+use crate::prg::{AesCtr, Prg};
+
+/// The security level of the protocol.
+/// It fixes the primitives and the sizes of all the byte arrays,
+/// so that arrays of different sizes cannot be mixed up.
+pub trait SecurityParams:
+    'static + Debug + Clone + Copy + Default + PartialEq + Eq + Send + Sync
+{
+    /// The size of the seeds, i.e., the key size of the PRG.
+    type KeySize: ArrayLength<u8> + Debug;
+    /// The size of the IV of the PRG.
+    type BlockSize: ArrayLength<u8> + Debug;
+    /// The size of the openings of the commitments.
+    type OpeningSize: ArrayLength<u8> + Debug;
+    /// The size of the digests, the IV and the Fiat-Shamir RNG seed
+    /// are taken from a digest so it must not be smaller than either of them.
+    type DigestSize: ArrayLength<u8>
+        + Debug
+        + IsGreaterOrEqual<Self::BlockSize, Output = True>
+        + IsGreaterOrEqual<U32, Output = True>;
+
+    /// The PRG used to expand the seeds.
+    type Prg: Prg<Self::KeySize, Self::BlockSize>;
+    /// The hash function used for the commitments and the transcript.
+    type Hash: ProtocolHash<Self::DigestSize>;
+    /// The identifier of the security level in the proof encoding.
+    const ID: u8;
+}
+
+/// A seed of the PRG.
+pub type Key<S> = GenericArray<u8, <S as SecurityParams>::KeySize>;
+
+/// An IV of the PRG.
+pub type Block<S> = GenericArray<u8, <S as SecurityParams>::BlockSize>;
+
+/// The opening of a commitment.
+pub type OpeningArray<S> = GenericArray<u8, <S as SecurityParams>::OpeningSize>;
+
+/// The output of the hash function.
+pub type DigestOutput<S> = GenericArray<u8, <S as SecurityParams>::DigestSize>;
+
+pub(crate) fn key_size<S: SecurityParams>() -> usize {
+    S::KeySize::USIZE
+}
+
+pub(crate) fn digest_size<S: SecurityParams>() -> usize {
+    S::DigestSize::USIZE
+}
+
+pub(crate) fn hash_id<S: SecurityParams>() -> u8 {
+    <S::Hash as ProtocolHash<S::DigestSize>>::ID
+}
+
+pub(crate) fn prg_id<S: SecurityParams>() -> u8 {
+    <S::Prg as Prg<S::KeySize, S::BlockSize>>::ID
+}
+
+/// The level markers only carry the hash function and the PRG as types,
+/// so the usual traits are implemented without any bound on them.
+macro_rules! impl_level_marker {
+    ($name:ident) => {
+        impl<H, P> Debug for $name<H, P> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(stringify!($name))
+            }
+        }
+
+        impl<H, P> Clone for $name<H, P> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<H, P> Copy for $name<H, P> {}
+
+        impl<H, P> Default for $name<H, P> {
+            fn default() -> Self {
+                Self(PhantomData)
+            }
+        }
+
+        impl<H, P> PartialEq for $name<H, P> {
+            fn eq(&self, _other: &Self) -> bool {
+                true
+            }
+        }
+
+        impl<H, P> Eq for $name<H, P> {}
+    };
+}
+
+/// 128 bits of security, using AES-128 and SHA3-256 by default.
+/// Any hash function with 32 byte digests and any PRG with 16 byte seeds
+/// can be used, e.g., `L1<Shake128, Shake>`.
+pub struct L1<H = Sha3_256, P = AesCtr>(PhantomData<fn() -> (H, P)>);
+
+impl_level_marker!(L1);
+
+impl<H: ProtocolHash<U32>, P: Prg<U16, U16>> SecurityParams for L1<H, P> {
+    type KeySize = U16;
+    type BlockSize = U16;
+    type OpeningSize = U16;
+    type DigestSize = U32;
+    type Prg = P;
+    type Hash = H;
+    const ID: u8 = 1;
+}
+
+/// 256 bits of security, using AES-256 and SHA3-512 by default.
+/// Any hash function with 64 byte digests and any PRG with 32 byte seeds
+/// can be used, e.g., `L5<Shake256, Shake>`.
+pub struct L5<H = Sha3_512, P = AesCtr>(PhantomData<fn() -> (H, P)>);
+
+impl_level_marker!(L5);
+
+impl<H: ProtocolHash<U64>, P: Prg<U32, U16>> SecurityParams for L5<H, P> {
+    type KeySize = U32;
+    type BlockSize = U16;
+    type OpeningSize = U32;
+    type DigestSize = U64;
+    type Prg = P;
+    type Hash = H;
+    const ID: u8 = 5;
+}
+
+/// Serde helper to (de)serialize a `GenericArray` using hex.
+pub(crate) mod hex_array {
+    use generic_array::{ArrayLength, GenericArray};
+    use serde::{de, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S, N>(a: &GenericArray<u8, N>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        N: ArrayLength<u8>,
+    {
+        hex::serde::serialize(a.as_slice(), serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D, N>(deserializer: D) -> Result<GenericArray<u8, N>, D::Error>
+    where
+        D: Deserializer<'de>,
+        N: ArrayLength<u8>,
+    {
+        let bytes: Vec<u8> = hex::serde::deserialize(deserializer)?;
+        if bytes.len() != N::USIZE {
+            return Err(de::Error::invalid_length(
+                bytes.len(),
+                &"a fixed size array",
+            ));
+        }
+        Ok(GenericArray::clone_from_slice(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(key_size::<L1>(), 16);
+        assert_eq!(OpeningArray::<L1>::default().len(), 16);
+        assert_eq!(digest_size::<L1>(), 32);
+        assert_eq!(key_size::<L5>(), 32);
+        assert_eq!(OpeningArray::<L5>::default().len(), 32);
+        assert_eq!(digest_size::<L5>(), 64);
+    }
+}
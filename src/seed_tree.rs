@@ -0,0 +1,209 @@
+use crate::primitives::prg_double;
+use crate::{Block, Key, SecurityParams};
+
+/// A GGM tree of seeds in heap layout, i.e.,
+/// the children of node `i` are `2 * i + 1` and `2 * i + 2`.
+/// A tree with `n` leaves has `2 * n - 1` nodes and the leaves
+/// are the last `n` nodes, i.e., the nodes of the last level from left to right
+/// followed by the leaves of the level above if the tree is unbalanced.
+/// The tree can be unbalanced, in that case the leaves are at two different depths.
+pub(crate) struct SeedTree<S: SecurityParams> {
+    nodes: Vec<Key<S>>,
+    leaf_count: usize,
+}
+
+impl<S: SecurityParams> SeedTree<S> {
+    /// Expand `root` into a tree with `leaf_count` leaves.
+    pub(crate) fn expand(root: &Key<S>, iv: &Block<S>, leaf_count: usize) -> Self {
+        assert!(leaf_count >= 1);
+        let mut nodes = Vec::with_capacity(2 * leaf_count - 1);
+        nodes.push(root.clone());
+        for i in 0..leaf_count - 1 {
+            let (left, right) = prg_double::<S>(&nodes[i], iv);
+            nodes.push(left);
+            nodes.push(right);
+        }
+        Self { nodes, leaf_count }
+    }
+
+    pub(crate) fn leaves(&self) -> &[Key<S>] {
+        &self.nodes[self.leaf_count - 1..]
+    }
+
+    /// Output the smallest set of nodes that reveals all the leaves
+    /// except the ones in `hidden`, the nodes are ordered by their position.
+    pub(crate) fn open_all_but(&self, hidden: &[usize]) -> Vec<Key<S>> {
+        revealed_positions(self.leaf_count, hidden)
+            .into_iter()
+            .map(|i| self.nodes[i].clone())
+            .collect()
+    }
+
+    /// Reconstruct the leaves from the output of `open_all_but`,
+    /// the leaves in `hidden` are None.
+    /// None is returned if the number of nodes does not match
+    /// or if a hidden leaf is out of range.
+    pub(crate) fn reconstruct(
+        path: &[Key<S>],
+        hidden: &[usize],
+        iv: &Block<S>,
+        leaf_count: usize,
+    ) -> Option<Vec<Option<Key<S>>>> {
+        if leaf_count == 0 || hidden.iter().any(|j| *j >= leaf_count) {
+            return None;
+        }
+        let positions = revealed_positions(leaf_count, hidden);
+        if positions.len() != path.len() {
+            return None;
+        }
+
+        let mut nodes = vec![None; 2 * leaf_count - 1];
+        for (i, node) in positions.into_iter().zip(path) {
+            nodes[i] = Some(node.clone());
+        }
+        // the parent always comes before its children
+        for i in 0..leaf_count - 1 {
+            if let Some(node) = &nodes[i] {
+                let (left, right) = prg_double::<S>(node, iv);
+                nodes[2 * i + 1] = Some(left);
+                nodes[2 * i + 2] = Some(right);
+            }
+        }
+        Some(nodes.split_off(leaf_count - 1))
+    }
+
+    /// An upper bound on the number of nodes output by `open_all_but`
+    /// when `hidden_count` leaves are hidden.
+    pub(crate) fn max_open_size(leaf_count: usize, hidden_count: usize) -> usize {
+        if hidden_count == 0 {
+            return 1;
+        }
+        // every hidden internal node reveals two children minus the hidden ones,
+        // so the nodes revealed are the hidden internal nodes minus the hidden leaves plus one,
+        // at most hidden_count internal nodes can be hidden on every level
+        let internal_count = leaf_count - 1;
+        let mut hidden_internal = 0;
+        let mut level_start = 0;
+        while level_start < internal_count {
+            let level_end = (2 * level_start + 1).min(internal_count);
+            hidden_internal += (level_end - level_start).min(hidden_count);
+            level_start = 2 * level_start + 1;
+        }
+        (hidden_internal + 1).saturating_sub(hidden_count.min(leaf_count))
+    }
+}
+
+/// The positions of the nodes that reveal all the leaves except `hidden`,
+/// i.e., the nodes that are not hidden but whose parent is hidden.
+/// A node is hidden if it is a hidden leaf or an ancestor of one.
+fn revealed_positions(leaf_count: usize, hidden: &[usize]) -> Vec<usize> {
+    let mut is_hidden = vec![false; 2 * leaf_count - 1];
+    for j in hidden {
+        let mut i = leaf_count - 1 + j;
+        loop {
+            is_hidden[i] = true;
+            if i == 0 {
+                break;
+            }
+            i = (i - 1) / 2;
+        }
+    }
+    (0..is_hidden.len())
+        .filter(|i| !is_hidden[*i] && (*i == 0 || is_hidden[(*i - 1) / 2]))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{L1, L5};
+
+    fn check_seed_tree<S: SecurityParams>() {
+        let root = Key::<S>::default();
+        let iv = Block::<S>::default();
+        for leaf_count in 1..=17 {
+            let tree = SeedTree::<S>::expand(&root, &iv, leaf_count);
+            assert_eq!(tree.leaves().len(), leaf_count);
+
+            for ell in 0..leaf_count {
+                let path = tree.open_all_but(&[ell]);
+                assert!(path.len() <= SeedTree::<S>::max_open_size(leaf_count, 1));
+
+                let leaves = SeedTree::<S>::reconstruct(&path, &[ell], &iv, leaf_count).unwrap();
+                for (j, leaf) in leaves.iter().enumerate() {
+                    if j == ell {
+                        assert_eq!(*leaf, None);
+                    } else {
+                        assert_eq!(leaf.as_ref(), Some(&tree.leaves()[j]));
+                    }
+                }
+
+                // the hidden leaf must not be derivable from the path
+                assert!(!path.contains(&tree.leaves()[ell]));
+                assert!(!path.contains(&root));
+            }
+        }
+    }
+
+    #[test]
+    fn test_leaf_order() {
+        // the root has the children a and b, and a has the children c and d,
+        // so the leaves of a tree with three leaves are b, c and d
+        let root = Key::<L1>::default();
+        let iv = Block::<L1>::default();
+        let (a, b) = prg_double::<L1>(&root, &iv);
+        let (c, d) = prg_double::<L1>(&a, &iv);
+        let tree = SeedTree::<L1>::expand(&root, &iv, 3);
+        assert_eq!(tree.leaves(), [b, c, d]);
+    }
+
+    #[test]
+    fn test_seed_tree() {
+        check_seed_tree::<L1>();
+        check_seed_tree::<L5>();
+    }
+
+    #[test]
+    fn test_open_all_but_many() {
+        let root = Key::<L1>::from([1u8; 16]);
+        let iv = Block::<L1>::default();
+        let leaf_count = 13;
+        let tree = SeedTree::<L1>::expand(&root, &iv, leaf_count);
+
+        let hidden = [0, 5, 6, 12];
+        let path = tree.open_all_but(&hidden);
+        assert!(path.len() <= SeedTree::<L1>::max_open_size(leaf_count, hidden.len()));
+        let leaves = SeedTree::<L1>::reconstruct(&path, &hidden, &iv, leaf_count).unwrap();
+        for (j, leaf) in leaves.iter().enumerate() {
+            assert_eq!(leaf.is_none(), hidden.contains(&j));
+        }
+
+        // nothing hidden reveals the root
+        assert_eq!(tree.open_all_but(&[]), vec![root]);
+
+        // a path of the wrong length or an out of range leaf is rejected
+        assert!(SeedTree::<L1>::reconstruct(&path[1..], &hidden, &iv, leaf_count).is_none());
+        assert!(SeedTree::<L1>::reconstruct(&path, &[13], &iv, leaf_count).is_none());
+    }
+
+    #[test]
+    fn test_max_open_size() {
+        // one hidden leaf reveals the co-path, which is the depth of the tree
+        assert_eq!(SeedTree::<L1>::max_open_size(1, 1), 0);
+        assert_eq!(SeedTree::<L1>::max_open_size(2, 1), 1);
+        assert_eq!(SeedTree::<L1>::max_open_size(4, 1), 2);
+        assert_eq!(SeedTree::<L1>::max_open_size(5, 1), 3);
+        assert_eq!(SeedTree::<L1>::max_open_size(256, 1), 8);
+
+        // the bound holds for every hidden set of a small tree
+        for leaf_count in 1..=10 {
+            let tree =
+                SeedTree::<L1>::expand(&Key::<L1>::default(), &Block::<L1>::default(), leaf_count);
+            for mask in 0u32..1 << leaf_count {
+                let hidden: Vec<_> = (0..leaf_count).filter(|j| mask >> j & 1 == 1).collect();
+                let bound = SeedTree::<L1>::max_open_size(leaf_count, hidden.len());
+                assert!(tree.open_all_but(&hidden).len() <= bound);
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam::channel::{bounded, Receiver, Sender};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+
+use crate::{
+    io::wrap_tcpstream,
+    verifier::{IVerifier, Verifier},
+    Instance, InternalError, Param, ProverMsg, SecurityParams, VerifierMsg, L1,
+};
+
+/// The number of worker threads used by default.
+const DEFAULT_WORKERS: usize = 4;
+
+/// The session timeout used by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The outcome of one prover session, sent by `VerifierServer`.
+#[derive(Debug)]
+pub struct SessionReport {
+    /// The address of the prover.
+    pub peer: SocketAddr,
+    /// Whether the proof is accepted, or the reason the session failed.
+    pub outcome: Result<bool, InternalError>,
+}
+
+/// A server that verifies the proofs of many concurrent provers
+/// on a `TcpListener`, every connection is one session of `IVerifier`.
+/// The sessions run on a fixed number of worker threads,
+/// so connections wait in a queue when all workers are busy.
+pub struct VerifierServer<S: SecurityParams = L1> {
+    instance: Instance<S>,
+    param: Param,
+    workers: usize,
+    timeout: Duration,
+}
+
+impl<S: SecurityParams> VerifierServer<S> {
+    pub fn new(instance: Instance<S>, param: Param) -> Self {
+        Self {
+            instance,
+            param,
+            workers: DEFAULT_WORKERS,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set the number of worker threads, it must be positive.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        assert!(workers > 0, "a server needs at least one worker");
+        self.workers = workers;
+        self
+    }
+
+    /// Set the maximum duration of a session,
+    /// a session that does not finish in time is reported as `InternalError::Timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Accept connections on `listener` in a background thread
+    /// and send the outcome of every session to `reports`.
+    /// The server keeps running until `ServerHandle::shutdown` is called,
+    /// which outputs the errors of accepting connections.
+    pub fn spawn(
+        self,
+        listener: TcpListener,
+        reports: Sender<SessionReport>,
+    ) -> io::Result<ServerHandle> {
+        let addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let server = Arc::new(self);
+
+        let (stream_s, stream_r) = bounded::<TcpStream>(server.workers);
+        let workers: Vec<_> = (0..server.workers)
+            .map(|_| {
+                let server = server.clone();
+                let stream_r = stream_r.clone();
+                let reports = reports.clone();
+                thread::spawn(move || server.worker(stream_r, reports))
+            })
+            .collect();
+
+        let accept_stopped = stopped.clone();
+        let hdl = thread::spawn(move || {
+            // a failed accept does not stop the server, e.g., if the prover is already gone
+            let mut accept_errors = vec![];
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        if stream_s.send(stream).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => accept_errors.push(e),
+                }
+            }
+            // the workers finish the queued sessions and stop
+            drop(stream_s);
+            for worker in workers {
+                worker.join().expect("worker thread panicked");
+            }
+            accept_errors
+        });
+
+        Ok(ServerHandle { addr, stopped, hdl })
+    }
+
+    /// Run the sessions received from `streams` until the channel is closed.
+    fn worker(&self, streams: Receiver<TcpStream>, reports: Sender<SessionReport>) {
+        for stream in streams {
+            let peer = match stream.peer_addr() {
+                Ok(peer) => peer,
+                // the prover is already gone
+                Err(_) => continue,
+            };
+            let outcome = self.run_session(stream);
+            // the owner of the server may not listen to the reports
+            let _ = reports.send(SessionReport { peer, outcome });
+        }
+    }
+
+    fn run_session(&self, stream: TcpStream) -> Result<bool, InternalError> {
+        // a prover that does not read must not block the writer thread forever
+        stream.set_write_timeout(Some(self.timeout))?;
+        let (tx, rx, shutdown_sender, handle) = wrap_tcpstream::<VerifierMsg, ProverMsg<S>>(stream);
+
+        let verifier = Verifier::new(self.instance.clone(), self.param);
+        let mut iverifier = IVerifier::new(verifier, tx, rx).with_timeout(self.timeout);
+        let mut rng = ChaChaRng::from_entropy();
+        let outcome = iverifier.blocking_run(&mut rng);
+
+        // the connection is closed on every path, which also stops the reader thread
+        let _ = shutdown_sender.send(());
+        let io_res = handle.join().expect("tcp thread panicked");
+
+        match (outcome, io_res) {
+            // the channels are closed because of the connection error,
+            // or the verdict may not have reached the prover
+            (Err(InternalError::RecvError(_) | InternalError::SendError) | Ok(_), Err(e)) => {
+                Err(e.into())
+            }
+            (outcome, _) => outcome,
+        }
+    }
+}
+
+/// The handle of a running `VerifierServer`.
+pub struct ServerHandle {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    hdl: JoinHandle<Vec<io::Error>>,
+}
+
+impl ServerHandle {
+    /// The address the server listens on,
+    /// useful when the listener is bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting connections, wait for the running sessions to finish
+    /// and output the errors of accepting connections while the server was running.
+    pub fn shutdown(self) -> Vec<io::Error> {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wake up the accept loop, it fails if the server is already stopped
+        let _ = TcpStream::connect(self.addr);
+        self.hdl.join().expect("server thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crossbeam::channel::unbounded;
+
+    use super::*;
+    use crate::prover::{IProver, Prover};
+    use crate::{handshake::HelloReject, witness_instance_from_seed, Key, Witness};
+
+    /// Run one session as the prover,
+    /// output the address of the prover and the result of the session.
+    fn run_prover(addr: SocketAddr, prover: Prover) -> (SocketAddr, Result<bool, InternalError>) {
+        let stream = TcpStream::connect(addr).unwrap();
+        let local = stream.local_addr().unwrap();
+        let (tx, rx, shutdown_sender, handle) = wrap_tcpstream::<ProverMsg, VerifierMsg>(stream);
+        let mut iprover = IProver::from_prover(prover, tx, rx);
+        let res = iprover.blocking_run();
+        shutdown_sender.send(()).unwrap();
+        handle.join().unwrap().unwrap();
+        (local, res)
+    }
+
+    #[test]
+    fn test_server() {
+        let param = Param::default();
+        let (witness, instance) = witness_instance_from_seed::<L1>(&Key::<L1>::default(), param);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (report_s, report_r) = unbounded();
+        let server = VerifierServer::new(instance.clone(), param)
+            .with_workers(2)
+            .with_timeout(Duration::from_secs(60))
+            .spawn(listener, report_s)
+            .unwrap();
+        let addr = server.local_addr();
+
+        // the honest provers share the instance of the server
+        let honest_hdls: Vec<_> = (0..3u8)
+            .map(|i| {
+                let prover = Prover::from_witness_instance(
+                    witness.clone(),
+                    instance.clone(),
+                    Key::<L1>::from([i; 16]),
+                    param,
+                )
+                .unwrap();
+                thread::spawn(move || run_prover(addr, prover))
+            })
+            .collect();
+
+        // a prover with a wrong witness is rejected
+        let mut bad_witness = witness.get_bits().to_vec();
+        bad_witness[0] ^= 1;
+        let cheater = Prover::from_witness_instance_unchecked(
+            Witness::from_bits(bad_witness).unwrap(),
+            instance.clone(),
+            Key::<L1>::default(),
+            param,
+        );
+        let (cheater_addr, res) = run_prover(addr, cheater);
+        assert_eq!(res, Ok(false));
+
+        // a prover of another instance is rejected in the handshake
+        let mut rng = ChaChaRng::from_entropy();
+        let other = Prover::new(&mut rng, param);
+        let (other_addr, res) = run_prover(addr, other);
+        let rejected = Err(InternalError::HandshakeRejected(HelloReject::Instance));
+        assert_eq!(res, rejected);
+
+        let mut outcomes = HashMap::new();
+        for _ in 0..5 {
+            let report = report_r.recv_timeout(Duration::from_secs(60)).unwrap();
+            outcomes.insert(report.peer, report.outcome);
+        }
+        assert_eq!(outcomes.remove(&cheater_addr), Some(Ok(false)));
+        assert_eq!(outcomes.remove(&other_addr), Some(rejected));
+        for hdl in honest_hdls {
+            let (honest_addr, res) = hdl.join().unwrap();
+            assert_eq!(res, Ok(true));
+            assert_eq!(outcomes.remove(&honest_addr), Some(Ok(true)));
+        }
+        assert!(outcomes.is_empty());
+
+        assert!(server.shutdown().is_empty());
+        // no session is started after the shutdown
+        assert!(report_r.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_server_timeout() {
+        let param = Param::default();
+        let (_, instance) = witness_instance_from_seed::<L1>(&Key::<L1>::default(), param);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (report_s, report_r) = unbounded();
+        let server = VerifierServer::new(instance, param)
+            .with_workers(1)
+            .with_timeout(Duration::from_millis(200))
+            .spawn(listener, report_s)
+            .unwrap();
+
+        // a prover that never sends anything times out
+        let idle = TcpStream::connect(server.local_addr()).unwrap();
+        let report = report_r.recv_timeout(Duration::from_secs(60)).unwrap();
+        assert_eq!(report.peer, idle.local_addr().unwrap());
+        assert_eq!(report.outcome, Err(InternalError::Timeout));
+
+        drop(idle);
+        assert!(server.shutdown().is_empty());
+    }
+}
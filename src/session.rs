@@ -0,0 +1,567 @@
+use std::{
+    mem,
+    ops::{Deref, DerefMut},
+};
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    handshake::Hello,
+    prover::{AbortStats, Prover, ProverState},
+    verifier::Verifier,
+    DigestOutput, InternalError, Key, ProverMsg, SecurityParams, Step3Inner, VerifierMsg,
+};
+
+/// The stage of a `ProverSession`, i.e., what it waits for.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProverStage {
+    /// The session is not started, see `ProverSession::start`.
+    Start,
+    /// Waiting for the reply to the handshake.
+    AwaitHello,
+    /// Waiting for the first challenge J.
+    AwaitChal1,
+    /// Waiting for the second challenge L.
+    AwaitChal2,
+    /// Waiting for the verdict of the verifier.
+    AwaitVerdict,
+    /// The verifier accepts or rejects the proof.
+    Done(bool),
+    Failed(InternalError),
+}
+
+/// The prover side of the interactive protocol without any I/O.
+/// Every message from the verifier is passed to `handle`,
+/// which outputs the message to send back, if any.
+/// So it can be driven by any transport,
+/// e.g., `prover::IProver` drives it over channels.
+pub struct ProverSession<'a, S: SecurityParams> {
+    prover: SessionProver<'a, S>,
+    stage: ProverStage,
+    /// The state of the prover between step1 and step3.
+    state: Option<ProverState<S>>,
+    stats: AbortStats,
+}
+
+impl<'a, S: SecurityParams> ProverSession<'a, S> {
+    /// Create a session, the prover is restarted in place if an instance in J aborts given L,
+    /// at most `Param::max_restarts` times.
+    pub fn new(prover: &'a mut Prover<S>) -> Self {
+        Self::with_prover(SessionProver::Borrowed(prover))
+    }
+
+    fn with_prover(prover: SessionProver<'a, S>) -> Self {
+        Self {
+            prover,
+            stage: ProverStage::Start,
+            state: None,
+            stats: AbortStats::default(),
+        }
+    }
+
+    pub fn stage(&self) -> &ProverStage {
+        &self.stage
+    }
+
+    /// The abort statistics of the runs of step3 in this session.
+    pub fn stats(&self) -> AbortStats {
+        self.stats
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.stage, ProverStage::Done(_) | ProverStage::Failed(_))
+    }
+
+    /// Output the handshake, which is the first message of the session.
+    pub fn start(&mut self) -> Option<ProverMsg<S>> {
+        if self.stage != ProverStage::Start {
+            self.stage = ProverStage::Failed(InternalError::ProtocolError);
+            return None;
+        }
+        self.stage = ProverStage::AwaitHello;
+        let hello = Hello::new(self.prover.get_instance(), self.prover.get_param());
+        Some(ProverMsg::Hello(hello))
+    }
+
+    /// Handle a message from the verifier and output the reply, if any.
+    /// The session fails on an unexpected message,
+    /// see `finish` for the reason.
+    pub fn handle(&mut self, msg: VerifierMsg) -> Option<ProverMsg<S>> {
+        let stage = mem::replace(
+            &mut self.stage,
+            ProverStage::Failed(InternalError::ProtocolError),
+        );
+        match self.step(stage, msg) {
+            Ok((stage, reply)) => {
+                self.stage = stage;
+                reply
+            }
+            Err(e) => {
+                self.state = None;
+                self.stage = ProverStage::Failed(e);
+                None
+            }
+        }
+    }
+
+    /// Output the verdict of a finished session, or the reason it failed.
+    pub fn finish(self) -> Result<bool, InternalError> {
+        match self.stage {
+            ProverStage::Done(accepted) => Ok(accepted),
+            ProverStage::Failed(e) => Err(e),
+            _ => Err(InternalError::ProtocolError),
+        }
+    }
+
+    fn step(
+        &mut self,
+        stage: ProverStage,
+        msg: VerifierMsg,
+    ) -> Result<(ProverStage, Option<ProverMsg<S>>), InternalError> {
+        match (stage, msg) {
+            (ProverStage::AwaitHello, VerifierMsg::Hello(reply)) => {
+                reply.map_err(InternalError::HandshakeRejected)?;
+                Ok(self.step1())
+            }
+            (ProverStage::AwaitChal1, VerifierMsg::Step1(chal1)) => {
+                let mut state = self.state.take().ok_or(InternalError::ProtocolError)?;
+                let msg = ProverMsg::Step2(self.prover.step2(&mut state, &chal1)?);
+                self.state = Some(state);
+                Ok((ProverStage::AwaitChal2, Some(msg)))
+            }
+            (ProverStage::AwaitChal2, VerifierMsg::Step2(chal2)) => {
+                let mut state = self.state.take().ok_or(InternalError::ProtocolError)?;
+                let res = self.prover.step3(&mut state, &chal2);
+                self.stats.record(&state);
+                match res {
+                    // restart by sending a new first message
+                    Err(InternalError::Abort)
+                        if self.stats.restart_count < self.prover.get_param().max_restarts() =>
+                    {
+                        self.stats.restart_count += 1;
+                        self.prover.restart();
+                        Ok(self.step1())
+                    }
+                    res => Ok((ProverStage::AwaitVerdict, Some(ProverMsg::Step3(res?)))),
+                }
+            }
+            (ProverStage::AwaitVerdict, VerifierMsg::Verdict(accepted)) => {
+                Ok((ProverStage::Done(accepted), None))
+            }
+            _ => Err(InternalError::ProtocolError),
+        }
+    }
+
+    fn step1(&mut self) -> (ProverStage, Option<ProverMsg<S>>) {
+        let state = self.prover.step1();
+        let h = state.get_h();
+        self.state = Some(state);
+        (ProverStage::AwaitChal1, Some(ProverMsg::Step1(h)))
+    }
+}
+
+impl<S: SecurityParams> ProverSession<'static, S> {
+    /// Create a session that owns the prover,
+    /// so that it can be moved to another thread between the messages,
+    /// e.g., `async_io::AsyncIProver` handles every message on the blocking thread pool of tokio.
+    pub fn from_prover(prover: Prover<S>) -> Self {
+        Self::with_prover(SessionProver::Owned(prover))
+    }
+}
+
+/// The prover of a session, borrowed from the driver or owned by the session.
+enum SessionProver<'a, S: SecurityParams> {
+    Borrowed(&'a mut Prover<S>),
+    Owned(Prover<S>),
+}
+
+impl<S: SecurityParams> Deref for SessionProver<'_, S> {
+    type Target = Prover<S>;
+
+    fn deref(&self) -> &Prover<S> {
+        match self {
+            SessionProver::Borrowed(prover) => prover,
+            SessionProver::Owned(prover) => prover,
+        }
+    }
+}
+
+impl<S: SecurityParams> DerefMut for SessionProver<'_, S> {
+    fn deref_mut(&mut self) -> &mut Prover<S> {
+        match self {
+            SessionProver::Borrowed(prover) => prover,
+            SessionProver::Owned(prover) => prover,
+        }
+    }
+}
+
+/// The stage of a `VerifierSession`, i.e., what it waits for.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifierStage {
+    /// Waiting for the handshake.
+    AwaitHello,
+    /// Waiting for the first prover message h.
+    AwaitStep1,
+    /// Waiting for the second prover message.
+    AwaitStep2,
+    /// Waiting for the openings, or a new h if the prover restarts.
+    AwaitStep3,
+    /// The transcript is checked outside of the session, see `VerifierSession::take_transcript`.
+    Verifying,
+    /// The proof is accepted or rejected.
+    Done(bool),
+    Failed(InternalError),
+}
+
+/// What the driver of a `VerifierSession` does after a prover message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Send the message and wait for the next prover message.
+    Send(VerifierMsg),
+    /// Send the last message of the session.
+    Finish(VerifierMsg),
+    /// The session failed without a message for the prover.
+    Close,
+}
+
+/// The messages and the challenges of a session that `Verifier::verify` checks,
+/// it owns them so that the check can run on another thread.
+pub struct Transcript<S: SecurityParams> {
+    h: DigestOutput<S>,
+    chal1: Vec<usize>,
+    h_prime: DigestOutput<S>,
+    mseeds: Vec<Key<S>>,
+    chal2: Vec<usize>,
+    openings: Vec<Step3Inner<S>>,
+}
+
+impl<S: SecurityParams> Transcript<S> {
+    pub fn verify(&self, verifier: &Verifier<S>) -> bool {
+        verifier.verify(
+            &self.h,
+            &self.chal1,
+            &self.h_prime,
+            &self.mseeds,
+            &self.chal2,
+            &self.openings,
+        )
+    }
+}
+
+/// The verifier side of the interactive protocol without any I/O,
+/// see `ProverSession`.
+pub struct VerifierSession<'a, S: SecurityParams, R> {
+    verifier: &'a Verifier<S>,
+    rng: &'a mut R,
+    stage: VerifierStage,
+    restart_count: usize,
+    h: DigestOutput<S>,
+    chal1: Vec<usize>,
+    step2: (DigestOutput<S>, Vec<Key<S>>),
+    chal2: Vec<usize>,
+}
+
+impl<'a, S: SecurityParams, R: CryptoRng + RngCore> VerifierSession<'a, S, R> {
+    /// Create a session, the challenges are sampled from `rng`.
+    pub fn new(verifier: &'a Verifier<S>, rng: &'a mut R) -> Self {
+        Self {
+            verifier,
+            rng,
+            stage: VerifierStage::AwaitHello,
+            restart_count: 0,
+            h: DigestOutput::<S>::default(),
+            chal1: vec![],
+            step2: (DigestOutput::<S>::default(), vec![]),
+            chal2: vec![],
+        }
+    }
+
+    pub fn stage(&self) -> &VerifierStage {
+        &self.stage
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.stage,
+            VerifierStage::Done(_) | VerifierStage::Failed(_)
+        )
+    }
+
+    /// Handle a message from the prover and output what to do next.
+    pub fn handle(&mut self, msg: ProverMsg<S>) -> Action {
+        let stage = mem::replace(
+            &mut self.stage,
+            VerifierStage::Failed(InternalError::ProtocolError),
+        );
+        let (stage, action) = self.step(stage, msg);
+        self.stage = stage;
+        action
+    }
+
+    /// Take the transcript if `msg` holds the openings that the session waits for,
+    /// otherwise give `msg` back to be handled by `handle`.
+    /// The driver checks the transcript itself, e.g., on a blocking thread,
+    /// and passes the result to `conclude`.
+    pub fn take_transcript(&mut self, msg: ProverMsg<S>) -> Result<Transcript<S>, ProverMsg<S>> {
+        match (&self.stage, msg) {
+            (VerifierStage::AwaitStep3, ProverMsg::Step3(openings)) => {
+                self.stage = VerifierStage::Verifying;
+                Ok(self.transcript(openings))
+            }
+            (_, msg) => Err(msg),
+        }
+    }
+
+    /// Finish the session with the result of `Transcript::verify`.
+    pub fn conclude(&mut self, accepted: bool) -> Action {
+        if self.stage != VerifierStage::Verifying {
+            self.stage = VerifierStage::Failed(InternalError::ProtocolError);
+            return Action::Close;
+        }
+        self.stage = VerifierStage::Done(accepted);
+        Action::Finish(VerifierMsg::Verdict(accepted))
+    }
+
+    /// Output whether the proof is accepted, or the reason the session failed.
+    pub fn finish(self) -> Result<bool, InternalError> {
+        match self.stage {
+            VerifierStage::Done(accepted) => Ok(accepted),
+            VerifierStage::Failed(e) => Err(e),
+            _ => Err(InternalError::ProtocolError),
+        }
+    }
+
+    fn step(&mut self, stage: VerifierStage, msg: ProverMsg<S>) -> (VerifierStage, Action) {
+        match (stage, msg) {
+            (VerifierStage::AwaitHello, ProverMsg::Hello(hello)) => {
+                let reply = hello.check(self.verifier.get_instance(), self.verifier.get_param());
+                match reply {
+                    Ok(()) => (
+                        VerifierStage::AwaitStep1,
+                        Action::Send(VerifierMsg::Hello(reply)),
+                    ),
+                    Err(ref reason) => (
+                        VerifierStage::Failed(InternalError::HandshakeRejected(reason.clone())),
+                        Action::Finish(VerifierMsg::Hello(reply)),
+                    ),
+                }
+            }
+            (VerifierStage::AwaitStep1, ProverMsg::Step1(h)) => self.chal1(h),
+            // a new first message instead of the openings means
+            // that the prover restarts because an instance in J aborts given L,
+            // every restart gives a cheating prover another chance at J and L
+            (VerifierStage::AwaitStep3, ProverMsg::Step1(h))
+                if self.restart_count < self.verifier.get_param().max_restarts() =>
+            {
+                self.restart_count += 1;
+                self.chal1(h)
+            }
+            (VerifierStage::AwaitStep2, ProverMsg::Step2(step2)) => {
+                self.step2 = step2;
+                self.chal2 = self.verifier.step2(self.rng);
+                (
+                    VerifierStage::AwaitStep3,
+                    Action::Send(VerifierMsg::Step2(self.chal2.clone())),
+                )
+            }
+            (VerifierStage::AwaitStep3, ProverMsg::Step3(openings)) => {
+                let accepted = self.transcript(openings).verify(self.verifier);
+                (
+                    VerifierStage::Done(accepted),
+                    Action::Finish(VerifierMsg::Verdict(accepted)),
+                )
+            }
+            _ => (
+                VerifierStage::Failed(InternalError::ProtocolError),
+                Action::Close,
+            ),
+        }
+    }
+
+    fn transcript(&mut self, openings: Vec<Step3Inner<S>>) -> Transcript<S> {
+        let (h_prime, mseeds) = mem::take(&mut self.step2);
+        Transcript {
+            h: self.h.clone(),
+            chal1: mem::take(&mut self.chal1),
+            h_prime,
+            mseeds,
+            chal2: mem::take(&mut self.chal2),
+            openings,
+        }
+    }
+
+    fn chal1(&mut self, h: DigestOutput<S>) -> (VerifierStage, Action) {
+        self.h = h;
+        self.chal1 = self.verifier.step1(self.rng);
+        (
+            VerifierStage::AwaitStep2,
+            Action::Send(VerifierMsg::Step1(self.chal1.clone())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::{handshake::HelloReject, Param, L1};
+
+    /// Pass the messages between the two sessions in memory.
+    fn run_in_memory<R: CryptoRng + RngCore>(
+        prover_session: &mut ProverSession<L1>,
+        verifier_session: &mut VerifierSession<L1, R>,
+    ) {
+        let mut msg = prover_session.start();
+        while let Some(prover_msg) = msg.take() {
+            match verifier_session.handle(prover_msg) {
+                Action::Send(reply) | Action::Finish(reply) => {
+                    msg = prover_session.handle(reply);
+                }
+                Action::Close => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
+
+        let mut prover_session = ProverSession::new(&mut prover);
+        let mut verifier_session = VerifierSession::new(&verifier, &mut rng);
+        assert_eq!(prover_session.stage(), &ProverStage::Start);
+        run_in_memory(&mut prover_session, &mut verifier_session);
+        assert_eq!(prover_session.stage(), &ProverStage::Done(true));
+        assert!(verifier_session.is_finished());
+        assert!(prover_session.stats().instance_count >= param.rep_param);
+        assert_eq!(prover_session.finish(), Ok(true));
+        assert_eq!(verifier_session.finish(), Ok(true));
+
+        // the handshake is rejected for another parameter
+        let other = Verifier::new(prover.get_instance().clone(), Param::L1_FAST);
+        let mut prover_session = ProverSession::new(&mut prover);
+        let mut verifier_session = VerifierSession::new(&other, &mut rng);
+        run_in_memory(&mut prover_session, &mut verifier_session);
+        let reason = HelloReject::Param(Param::L1_FAST);
+        assert_eq!(
+            prover_session.finish(),
+            Err(InternalError::HandshakeRejected(reason.clone()))
+        );
+        assert_eq!(
+            verifier_session.finish(),
+            Err(InternalError::HandshakeRejected(reason))
+        );
+    }
+
+    #[test]
+    fn test_take_transcript() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
+
+        // the driver checks the openings itself
+        let mut prover_session = ProverSession::new(&mut prover);
+        let mut verifier_session = VerifierSession::new(&verifier, &mut rng);
+        let mut msg = prover_session.start();
+        while let Some(prover_msg) = msg.take() {
+            let action = match verifier_session.take_transcript(prover_msg) {
+                Ok(transcript) => {
+                    assert_eq!(verifier_session.stage(), &VerifierStage::Verifying);
+                    verifier_session.conclude(transcript.verify(&verifier))
+                }
+                Err(prover_msg) => verifier_session.handle(prover_msg),
+            };
+            match action {
+                Action::Send(reply) | Action::Finish(reply) => {
+                    msg = prover_session.handle(reply);
+                }
+                Action::Close => {}
+            }
+        }
+        assert_eq!(prover_session.finish(), Ok(true));
+        assert_eq!(verifier_session.finish(), Ok(true));
+
+        // a verdict without a transcript closes the session
+        let mut verifier_session = VerifierSession::new(&verifier, &mut rng);
+        assert_eq!(verifier_session.conclude(true), Action::Close);
+        assert_eq!(verifier_session.finish(), Err(InternalError::ProtocolError));
+    }
+
+    #[test]
+    fn test_restart_limit() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
+
+        let mut verifier_session = VerifierSession::new(&verifier, &mut rng);
+        let hello = ProverMsg::Hello(Hello::new(prover.get_instance(), param));
+        verifier_session.handle(hello);
+        let h = prover.step1().get_h();
+        assert!(matches!(
+            verifier_session.handle(ProverMsg::Step1(h)),
+            Action::Send(VerifierMsg::Step1(_))
+        ));
+        // every restart after L is answered with a new J, up to the limit
+        let step2 = || ProverMsg::Step2((h, vec![]));
+        for _ in 0..param.max_restarts() {
+            assert!(matches!(
+                verifier_session.handle(step2()),
+                Action::Send(VerifierMsg::Step2(_))
+            ));
+            assert!(matches!(
+                verifier_session.handle(ProverMsg::Step1(h)),
+                Action::Send(VerifierMsg::Step1(_))
+            ));
+        }
+        verifier_session.handle(step2());
+        assert_eq!(verifier_session.handle(ProverMsg::Step1(h)), Action::Close);
+        assert_eq!(verifier_session.finish(), Err(InternalError::ProtocolError));
+
+        // the prover only restarts once L is known
+        let mut verifier_session = VerifierSession::new(&verifier, &mut rng);
+        let hello = ProverMsg::Hello(Hello::new(prover.get_instance(), param));
+        verifier_session.handle(hello);
+        verifier_session.handle(ProverMsg::Step1(h));
+        assert_eq!(verifier_session.handle(ProverMsg::Step1(h)), Action::Close);
+    }
+
+    #[test]
+    fn test_unexpected_messages() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<L1>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
+
+        // a challenge before the handshake
+        let mut prover_session = ProverSession::new(&mut prover);
+        let hello = prover_session.start().unwrap();
+        assert_eq!(prover_session.handle(VerifierMsg::Step2(vec![])), None);
+        assert_eq!(
+            prover_session.stage(),
+            &ProverStage::Failed(InternalError::ProtocolError)
+        );
+        // a failed session stays failed
+        assert_eq!(prover_session.handle(VerifierMsg::Hello(Ok(()))), None);
+        assert!(prover_session.is_finished());
+
+        // a session cannot be started twice
+        let mut prover_session = ProverSession::new(&mut prover);
+        prover_session.start().unwrap();
+        assert_eq!(prover_session.start(), None);
+
+        // the verifier closes the session on a message out of order
+        let mut verifier_session = VerifierSession::new(&verifier, &mut rng);
+        assert!(matches!(
+            verifier_session.handle(hello.clone()),
+            Action::Send(VerifierMsg::Hello(Ok(())))
+        ));
+        assert_eq!(verifier_session.handle(hello), Action::Close);
+        assert_eq!(verifier_session.finish(), Err(InternalError::ProtocolError));
+    }
+}
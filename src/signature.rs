@@ -0,0 +1,199 @@
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::*,
+    errors::InternalError,
+    fiat_shamir::{NIProver, NIProverMsg, NIVerifier},
+    hash::ProtocolHash,
+    prover::Prover,
+    sanity_check, update_instance, witness_instance_from_seed, DigestOutput, Instance, Key, Param,
+    SecurityParams, Witness, L1,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+/// The public key of the signature scheme, i.e., the subset sum instance.
+pub struct PublicKey<S: SecurityParams = L1> {
+    instance: Instance<S>,
+    param: Param,
+}
+
+impl<S: SecurityParams> PublicKey<S> {
+    pub fn new(instance: Instance<S>, param: Param) -> Self {
+        Self { instance, param }
+    }
+
+    pub fn get_instance(&self) -> &Instance<S> {
+        &self.instance
+    }
+
+    pub fn get_param(&self) -> Param {
+        self.param
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(bound = "")]
+/// The secret key of the signature scheme, i.e., the witness,
+/// it also holds the public key since the prover needs the instance.
+pub struct SecretKey<S: SecurityParams = L1> {
+    witness: Witness,
+    public_key: PublicKey<S>,
+}
+
+impl<S: SecurityParams> SecretKey<S> {
+    /// Create a secret key from a given witness and public key.
+    /// This function performs a sanity check and outputs
+    /// an error if the check fails.
+    pub fn new(witness: Witness, public_key: PublicKey<S>) -> Result<Self, InternalError> {
+        sanity_check(&witness, &public_key.instance, public_key.param)?;
+        Ok(Self {
+            witness,
+            public_key,
+        })
+    }
+
+    pub fn get_public_key(&self) -> &PublicKey<S> {
+        &self.public_key
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent, bound = "")]
+/// A signature is a non-interactive proof
+/// where the public key and the message are bound into the challenges.
+pub struct Signature<S: SecurityParams = L1>(NIProverMsg<S>);
+
+impl<S: SecurityParams> Signature<S> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        NIProverMsg::from_bytes(bytes).map(Signature)
+    }
+}
+
+/// Hash the public key and the message,
+/// the output is used as the context of the Fiat-Shamir challenges.
+fn hash_message<S: SecurityParams>(pk: &PublicKey<S>, msg: &[u8]) -> DigestOutput<S> {
+    let mut hasher = S::Hash::new();
+    hasher.update(PREFIX_SIG_PK);
+    update_instance(&mut hasher, &pk.instance);
+
+    hasher.update(PREFIX_SIG_MSG);
+    hasher.update(msg.len().to_le_bytes());
+    hasher.update(msg);
+    hasher.finalize()
+}
+
+/// Generate a random key pair according to parameters `param`.
+pub fn keygen<S: SecurityParams, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    param: Param,
+) -> (SecretKey<S>, PublicKey<S>) {
+    let mut seed = Key::<S>::default();
+    rng.fill_bytes(&mut seed);
+    keygen_from_seed(&seed, param)
+}
+
+/// Deterministically generate a key pair from a secret `seed`,
+/// so that the secret key can be stored as just the seed.
+pub fn keygen_from_seed<S: SecurityParams>(
+    seed: &Key<S>,
+    param: Param,
+) -> (SecretKey<S>, PublicKey<S>) {
+    let (witness, instance) = witness_instance_from_seed(seed, param);
+    let public_key = PublicKey { instance, param };
+    let secret_key = SecretKey {
+        witness,
+        public_key: public_key.clone(),
+    };
+    (secret_key, public_key)
+}
+
+/// Sign the message `msg`, the master seed of the prover is sampled from `rng`.
+pub fn sign<S: SecurityParams, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    sk: &SecretKey<S>,
+    msg: &[u8],
+) -> Result<Signature<S>, InternalError> {
+    let pk = &sk.public_key;
+    let mut mseed = Key::<S>::default();
+    rng.fill_bytes(&mut mseed);
+    let prover =
+        Prover::from_witness_instance(sk.witness.clone(), pk.instance.clone(), mseed, pk.param)?;
+
+    let ctx = hash_message(pk, msg);
+    let proof = NIProver::from_prover(prover).prove_with_context(&ctx)?;
+    Ok(Signature(proof))
+}
+
+/// Verify the signature `sig` on the message `msg`.
+pub fn verify<S: SecurityParams>(
+    pk: &PublicKey<S>,
+    msg: &[u8],
+    sig: &Signature<S>,
+) -> Result<bool, InternalError> {
+    let ctx = hash_message(pk, msg);
+    NIVerifier::verify_with_context(pk.instance.clone(), pk.param, &ctx, &sig.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hash::Shake256;
+    use crate::L5;
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+
+    fn check_signature<S: SecurityParams>() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let (sk, pk) = keygen::<S, _>(&mut rng, param);
+
+        let msg = b"hello world";
+        let sig = sign(&mut rng, &sk, msg).unwrap();
+        assert_eq!(verify(&pk, msg, &sig), Ok(true));
+
+        let decoded = Signature::from_bytes(&sig.to_bytes()).unwrap();
+        assert_eq!(verify(&pk, msg, &decoded), Ok(true));
+
+        // a different message should fail
+        assert_eq!(verify(&pk, b"hello world!", &sig), Ok(false));
+
+        // a different public key should fail
+        let (_, other_pk) = keygen::<S, _>(&mut rng, param);
+        assert_eq!(verify(&other_pk, msg, &sig), Ok(false));
+
+        // keys generated from the same seed are the same
+        let seed = Key::<S>::default();
+        let (sk1, pk1) = keygen_from_seed::<S>(&seed, param);
+        let (_, pk2) = keygen_from_seed(&seed, param);
+        assert_eq!(pk1, pk2);
+        let sig = sign(&mut rng, &sk1, msg).unwrap();
+        assert_eq!(verify(&pk2, msg, &sig), Ok(true));
+
+        // a plain proof is not a signature, even for the empty message
+        let proof = NIProver::from_prover(
+            Prover::from_witness_instance(
+                sk.witness.clone(),
+                pk.instance.clone(),
+                Key::<S>::default(),
+                param,
+            )
+            .unwrap(),
+        )
+        .prove()
+        .unwrap();
+        assert_eq!(verify(&pk, b"", &Signature(proof)), Ok(false));
+    }
+
+    #[test]
+    fn test_signature() {
+        check_signature::<L1>();
+        check_signature::<L5>();
+        check_signature::<L5<Shake256>>();
+    }
+}
@@ -1,18 +1,40 @@
-use crate::consts::{BLOCK_SIZE, DIGEST_SIZE};
 use crate::errors::InternalError;
-use crate::{Param, ProverMsg, VerifierMsg};
-use crossbeam::channel::{Receiver, Sender};
+use crate::primitives::{commit, hash1, hash2, hash3, hash4, prg_pair, Commitment, Opening};
+use crate::prover::CncInstance;
+use crate::seed_tree::SeedTree;
+use crate::session::{Action, VerifierSession};
+use crate::{
+    compute_t_share, hash_instance, r_share_from_seed, Block, DigestOutput, Instance, Key, Param,
+    ProverMsg, SecurityParams, Step3Inner, VerifierMsg, L1,
+};
+use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender};
 use rand::seq::SliceRandom;
 use rand::Rng;
 use rand_core::{CryptoRng, RngCore};
+use std::time::{Duration, Instant};
 
-pub struct Verifier {
+pub struct Verifier<S: SecurityParams = L1> {
+    instance: Instance<S>,
+    iv: Block<S>,
     param: Param,
 }
 
-impl Verifier {
-    pub fn new(param: Param) -> Self {
-        Self { param }
+impl<S: SecurityParams> Verifier<S> {
+    pub fn new(instance: Instance<S>, param: Param) -> Self {
+        let iv = hash_instance(&instance);
+        Self {
+            instance,
+            iv,
+            param,
+        }
+    }
+
+    pub fn get_param(&self) -> Param {
+        self.param
+    }
+
+    pub fn get_instance(&self) -> &Instance<S> {
+        &self.instance
     }
 
     pub fn step1<R: CryptoRng + RngCore>(&self, rng: &mut R) -> Vec<usize> {
@@ -33,46 +55,247 @@ impl Verifier {
         chal2
     }
 
+    /// Verify the transcript of the protocol, i.e.,
+    /// the three prover messages and the two challenges.
+    /// `mseed_path` is the seed tree of the master seeds punctured at J.
     pub fn verify(
         &self,
-        h: &[u8; DIGEST_SIZE],
-        h_prime: &[u8; DIGEST_SIZE],
-        mseeds: &[[u8; BLOCK_SIZE]],
+        h: &DigestOutput<S>,
+        chal1: &[usize],
+        h_prime: &DigestOutput<S>,
+        mseed_path: &[Key<S>],
+        chal2: &[usize],
+        openings: &[Step3Inner<S>],
     ) -> bool {
-        // TODO unimplemented
-        true
+        let param = self.param;
+        if self.instance.weights.len() != param.ssp_dimension
+            || chal1.len() != param.rep_param
+            || chal2.len() != param.rep_param
+            || openings.len() != param.rep_param
+        {
+            return false;
+        }
+
+        // J must be a subset of [M] and L must be in [N]
+        let mut in_chal1 = vec![false; param.cnc_param];
+        for e in chal1 {
+            if *e >= param.cnc_param || in_chal1[*e] {
+                return false;
+            }
+            in_chal1[*e] = true;
+        }
+        if chal2.iter().any(|ell| *ell >= param.party_count) {
+            return false;
+        }
+
+        let mut h1s = vec![DigestOutput::<S>::default(); param.cnc_param];
+
+        // recompute h1 for the instances that are not in J from the master seeds
+        let mseeds = match SeedTree::<S>::reconstruct(mseed_path, chal1, &self.iv, param.cnc_param)
+        {
+            Some(mseeds) => mseeds,
+            None => return false,
+        };
+        for (e, mseed_inner) in mseeds.into_iter().enumerate() {
+            if let Some(mseed_inner) = mseed_inner {
+                h1s[e] = CncInstance::<S>::new(&mseed_inner, &self.iv, param).get_h1();
+            }
+        }
+
+        // reconstruct the views of the opened parties for the instances in J
+        let mut h_primes = Vec::with_capacity(param.rep_param);
+        for ((e, ell), opening) in chal1.iter().zip(chal2).zip(openings) {
+            match self.check_opening(*ell, opening) {
+                Some((h1, h_prime)) => {
+                    h1s[*e] = h1;
+                    h_primes.push(h_prime);
+                }
+                None => return false,
+            }
+        }
+
+        hash2::<S>(&h1s) == *h && hash4::<S, _>(h_primes.into_iter()) == *h_prime
+    }
+
+    /// Recompute h1 and the hash of the broadcast shares of one repetition,
+    /// output None if the opening is malformed or the shares do not sum to t.
+    fn check_opening(
+        &self,
+        ell: usize,
+        opening: &Step3Inner<S>,
+    ) -> Option<(DigestOutput<S>, DigestOutput<S>)> {
+        let param = self.param;
+        if opening.delta_rs.len() != param.ssp_dimension
+            || opening.xs_tilde.len() != param.ssp_dimension
+            || opening.xs_tilde.iter().any(|x| *x > 1)
+        {
+            return None;
+        }
+
+        // the commitments of the opened parties are recomputed,
+        // they are checked against h through h1
+        let leaves =
+            SeedTree::<S>::reconstruct(&opening.path, &[ell], &self.iv, param.party_count)?;
+        let mut coms = Vec::with_capacity(param.party_count);
+        let mut t_shares = Vec::with_capacity(param.party_count);
+        for (i, leaf) in leaves.iter().enumerate() {
+            let leaf = match leaf {
+                Some(leaf) => leaf,
+                None => {
+                    coms.push(Commitment::new(opening.com.clone()));
+                    t_shares.push(opening.t_share);
+                    continue;
+                }
+            };
+            let (seed, rho) = prg_pair::<S, S::KeySize, S::OpeningSize>(leaf, &self.iv);
+            coms.push(commit(&seed, &Opening::<S>::new(rho)));
+
+            let r_share = r_share_from_seed::<S>(&seed, &self.iv, param);
+            let delta_rs = if i == 0 {
+                Some(opening.delta_rs.as_slice())
+            } else {
+                None
+            };
+            t_shares.push(compute_t_share(
+                &self.instance,
+                &opening.xs_tilde,
+                &r_share,
+                delta_rs,
+            ));
+        }
+
+        // the broadcast shares must sum to t
+        let t = t_shares.iter().fold(0u64, |acc, s| acc.wrapping_add(*s));
+        if t != self.instance.t {
+            return None;
+        }
+
+        let h1 = hash1(&opening.delta_rs, &coms);
+        let h_prime = hash3::<S, _>(&opening.xs_tilde, t_shares.into_iter());
+        Some((h1, h_prime))
     }
 }
 
-pub struct IVerifier {
-    verifier: Verifier,
+pub struct IVerifier<S: SecurityParams = L1> {
+    verifier: Verifier<S>,
     tx: Sender<VerifierMsg>,
-    rx: Receiver<ProverMsg>,
+    rx: Receiver<ProverMsg<S>>,
+    timeout: Option<Duration>,
 }
 
-impl IVerifier {
-    pub fn new(verifier: Verifier, tx: Sender<VerifierMsg>, rx: Receiver<ProverMsg>) -> Self {
-        Self { verifier, tx, rx }
+impl<S: SecurityParams> IVerifier<S> {
+    pub fn new(verifier: Verifier<S>, tx: Sender<VerifierMsg>, rx: Receiver<ProverMsg<S>>) -> Self {
+        Self {
+            verifier,
+            tx,
+            rx,
+            timeout: None,
+        }
+    }
+
+    /// Limit the duration of a run, `InternalError::Timeout` is returned
+    /// if the prover does not finish the protocol within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Receive the next prover message before the deadline, if there is one.
+    fn recv(&self, deadline: Option<Instant>) -> Result<ProverMsg<S>, InternalError> {
+        match deadline {
+            Some(deadline) => self.rx.recv_deadline(deadline).map_err(|e| match e {
+                RecvTimeoutError::Timeout => InternalError::Timeout,
+                RecvTimeoutError::Disconnected => InternalError::RecvError(channel::RecvError),
+            }),
+            None => Ok(self.rx.recv()?),
+        }
     }
 
     pub fn blocking_run<R: CryptoRng + RngCore>(
         &mut self,
         rng: &mut R,
     ) -> Result<bool, InternalError> {
-        // first wait for the prover to send h
-        let h = match self.rx.recv()? {
-            ProverMsg::Step1(h) => h,
-            _ => return Err(InternalError::ProtocolError),
-        };
-        self.tx.send(VerifierMsg::Step1(self.verifier.step1(rng)))?;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut session = VerifierSession::new(&self.verifier, rng);
+        while !session.is_finished() {
+            match session.handle(self.recv(deadline)?) {
+                Action::Send(msg) | Action::Finish(msg) => self.tx.send(msg)?,
+                Action::Close => {}
+            }
+        }
+        session.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prover::Prover;
+    use crate::L5;
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+
+    fn check_verify<S: SecurityParams>() {
+        let mut rng = ChaChaRng::from_entropy();
+        let param = Param::default();
+        let mut prover = Prover::<S>::new(&mut rng, param);
+        let verifier = Verifier::new(prover.get_instance().clone(), param);
 
-        // wait for second message
-        let (h_prime, mseeds) = match self.rx.recv()? {
-            ProverMsg::Step2(inner) => inner,
-            _ => return Err(InternalError::ProtocolError),
+        // restart until no instance in J aborts given L
+        let (h, chal1, (h_prime, mseeds), chal2, openings) = loop {
+            let mut state = prover.step1();
+            let chal1 = verifier.step1(&mut rng);
+            let step2 = prover.step2(&mut state, &chal1).unwrap();
+            let chal2 = verifier.step2(&mut rng);
+            match prover.step3(&mut state, &chal2) {
+                Err(InternalError::Abort) => prover.restart(),
+                res => break (state.get_h(), chal1, step2, chal2, res.unwrap()),
+            }
         };
-        self.tx.send(VerifierMsg::Step2(self.verifier.step2(rng)))?;
+        assert!(verifier.verify(&h, &chal1, &h_prime, &mseeds, &chal2, &openings));
+
+        // a different challenge should fail
+        let mut bad_chal2 = chal2.clone();
+        bad_chal2[0] = (bad_chal2[0] + 1) % param.party_count;
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &mseeds, &bad_chal2, &openings));
+
+        // a modified master seed should fail
+        let mut bad_mseeds = mseeds.clone();
+        bad_mseeds[0][0] ^= 1;
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &bad_mseeds, &chal2, &openings));
+
+        // so should a path of the wrong length
+        let mut bad_mseeds = mseeds.clone();
+        bad_mseeds.pop();
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &bad_mseeds, &chal2, &openings));
+
+        // a modified broadcast share should fail
+        let mut bad_openings = openings.clone();
+        bad_openings[0].t_share = bad_openings[0].t_share.wrapping_add(1);
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &mseeds, &chal2, &bad_openings));
+
+        // a modified seed should fail
+        let mut bad_openings = openings.clone();
+        bad_openings[0].path[0][0] ^= 1;
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &mseeds, &chal2, &bad_openings));
+
+        // a malformed opening should fail instead of panic
+        let mut bad_openings = openings.clone();
+        bad_openings[0].xs_tilde.pop();
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &mseeds, &chal2, &bad_openings));
+        let mut bad_openings = openings.clone();
+        bad_openings[0].path.pop();
+        assert!(!verifier.verify(&h, &chal1, &h_prime, &mseeds, &chal2, &bad_openings));
+
+        // the verifier must use the same instance
+        let other = Prover::<S>::new(&mut rng, param);
+        let other_verifier = Verifier::new(other.get_instance().clone(), param);
+        assert!(!other_verifier.verify(&h, &chal1, &h_prime, &mseeds, &chal2, &openings));
+    }
 
-        Ok(self.verifier.verify(&h, &h_prime, &mseeds))
+    #[test]
+    fn test_verify() {
+        check_verify::<L1>();
+        check_verify::<L5>();
     }
 }